@@ -1,27 +1,128 @@
-use clap::Parser;
+use std::path::PathBuf;
 
-use crate::core::Provider;
+use clap::{Parser, Subcommand};
+
+use super::shell::Shell;
+use crate::core::{OutputFormat, Provider};
+use crate::tools::ApprovalMode;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
+#[allow(clippy::struct_excessive_bools)] // Command line flags
 pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// Enable tool usage
-    #[arg(long)]
+    #[arg(long, global = true)]
     pub enable_tools: Option<bool>,
 
     /// Maximum number of tool execution steps
-    #[arg(long)]
+    #[arg(long, global = true)]
     pub max_steps: Option<u32>,
 
-    /// Your query to the LLM
+    /// Your query to the LLM. Use `-` to read it from stdin; piped input is
+    /// otherwise attached as context. Starts an interactive chat when omitted
     #[arg()]
-    pub query: String,
+    pub query: Option<String>,
+
+    /// Start an interactive chat session, using the query as the first message
+    #[arg(short, long)]
+    pub chat: bool,
+
+    /// Resume the most recent session
+    #[arg(long = "continue", conflicts_with = "session")]
+    pub continue_session: bool,
+
+    /// Resume the named session, or start a new session with that name
+    #[arg(short, long, value_name = "NAME")]
+    pub session: Option<String>,
+
+    /// System prompt to use instead of the configured one (empty to disable)
+    #[arg(long, value_name = "PROMPT", conflicts_with = "system_file")]
+    pub system: Option<String>,
 
-    /// LLM provider to use (openai or claude)
-    #[arg(short, long, value_enum)]
+    /// Read the system prompt from a file
+    #[arg(long, value_name = "PATH")]
+    pub system_file: Option<PathBuf>,
+
+    /// Configuration file to load on top of the system, user and project files
+    #[arg(long, value_name = "PATH", global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Configuration profile to use
+    #[arg(short = 'P', long, value_name = "NAME", global = true)]
+    pub profile: Option<String>,
+
+    /// LLM provider to use (claude, openai or openai-compatible)
+    #[arg(short, long, value_enum, global = true)]
     pub provider: Option<Provider>,
 
+    /// Run every command not denied by a permission rule without asking
+    #[arg(short = 'y', long, conflicts_with = "never", global = true)]
+    pub yes: bool,
+
+    /// Refuse every command not allowed by a permission rule instead of asking
+    #[arg(long, global = true)]
+    pub never: bool,
+
+    /// How to write the response: formatted text, raw text, a JSON object
+    /// once it is complete, or a JSON event per line while it streams
+    #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
+    pub output: OutputFormat,
+
+    /// Show the token usage and estimated cost after each response
+    #[arg(long, global = true)]
+    pub usage: bool,
+
     /// Enable debug output
-    #[arg(short, long, default_value = "false")]
+    #[arg(short, long, default_value = "false", global = true)]
     pub debug: bool,
 }
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Explain the last failed shell command and offer a corrected one
+    Fix(FixArgs),
+    /// Suggest a single shell command for a task, printing only the command
+    Suggest {
+        /// What the command should do
+        #[arg(required = true)]
+        description: Vec<String>,
+    },
+    /// Print the shell integration for bash, zsh or fish
+    Init {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(clap::Args, Debug)]
+pub struct FixArgs {
+    /// The failed command, instead of the one recorded by the shell
+    /// integration or found in the history
+    #[arg(long, value_name = "COMMAND")]
+    pub command: Option<String>,
+
+    /// Exit code of the failed command
+    #[arg(long, value_name = "CODE")]
+    pub exit_code: Option<i32>,
+
+    /// File holding the error output of the command, instead of the one
+    /// captured by the shell integration or running the command again
+    #[arg(long, value_name = "PATH")]
+    pub stderr_file: Option<PathBuf>,
+}
+
+impl Args {
+    /// How commands without a matching permission rule are approved
+    pub const fn approval_mode(&self) -> ApprovalMode {
+        if self.yes {
+            ApprovalMode::Yes
+        } else if self.never {
+            ApprovalMode::Never
+        } else {
+            ApprovalMode::Prompt
+        }
+    }
+}
@@ -0,0 +1,480 @@
+use std::fs::File;
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use serde_json::{json, Value};
+
+use super::args::FixArgs;
+use super::input::fence;
+use super::run::{create_llm_client, create_tool_registry, print_usage, RunOptions};
+use super::shell::LastCommand;
+use crate::{
+    core::{conversation::ConversationManager, Config, Formatter, LLMError},
+    providers::Message,
+    tools::{
+        execute_command::configured_shell,
+        permissions::{Decision, Reason},
+        process::CappedOutput,
+        CommandHistoryTool, ExecuteCommandTool, PermissionPolicy, Tool,
+    },
+};
+
+/// Number of recent commands sent along as context
+const HISTORY_CONTEXT_LIMIT: usize = 10;
+/// Name of this program, whose own invocations are skipped in the history
+const PROGRAM_NAME: &str = env!("CARGO_PKG_NAME");
+
+/// Instructions sent together with the failed command
+const FIX_INSTRUCTIONS: &str = "The shell command below failed. Briefly explain why, then \
+    suggest a corrected command. End your answer with the corrected command alone in a \
+    ```sh code block, or leave out the code block if no command can fix it.";
+
+/// The failed command and what is known about its failure
+#[derive(Debug, Default)]
+struct FailedCommand {
+    command: String,
+    exit_code: Option<i32>,
+    /// File the shell integration copied the error output of the command to
+    stderr_file: Option<PathBuf>,
+    /// Output of the command, from a file or from running it again
+    output: Option<String>,
+}
+
+/// Explains the last failed command and offers to run the suggested fix.
+///
+/// The command comes from `--command`, the shell integration or the history,
+/// in that order. Its output is read from `--stderr-file` or the file the shell
+/// integration captured it in. Only if neither exists is the command run again,
+/// after the user confirmed it.
+pub(super) async fn run(
+    mut config: Config,
+    options: RunOptions,
+    args: FixArgs,
+) -> Result<(), LLMError> {
+    let formatter = Formatter::new(std::mem::take(&mut config.theme));
+    let client = create_llm_client(config.clone(), options.debug)?;
+    let registry = create_tool_registry(&config, &options).await?;
+    let mut conversation_manager = ConversationManager::new(client, registry, formatter);
+
+    let policy = PermissionPolicy::new(&config.tools.permissions, options.approval)?;
+    let history = recent_commands().await;
+    let mut failed = failed_command(
+        args.command,
+        args.exit_code,
+        LastCommand::recorded(),
+        &history,
+    )?;
+
+    match args.stderr_file.or_else(|| failed.stderr_file.take()) {
+        Some(path) => failed.output = Some(read_output(&path, config.tools.max_output_bytes)?),
+        None => capture_output(&mut failed, &config, policy.clone()).await?,
+    }
+
+    let mut stdout = io::stdout();
+    let messages = conversation_manager
+        .run(
+            vec![Message::user(fix_prompt(&failed, &history))],
+            options.max_steps(&config),
+            &mut stdout,
+        )
+        .await?;
+    writeln!(&mut stdout)?;
+    if options.show_usage {
+        print_usage(&conversation_manager.usage(), &config);
+    }
+
+    let suggestion = messages
+        .iter()
+        .rev()
+        .find_map(|message| match message {
+            Message::Assistant { content, .. } => Some(content.as_str()),
+            _ => None,
+        })
+        .and_then(suggested_command);
+    if let Some(suggestion) = suggestion {
+        offer_to_run(&suggestion, &configured_shell(&config.tools), &policy)?;
+    }
+    Ok(())
+}
+
+/// Returns the recent commands of the shell history, most recent first, or
+/// none if the history cannot be read
+async fn recent_commands() -> Vec<String> {
+    let entries = CommandHistoryTool
+        .execute(&json!({ "limit": HISTORY_CONTEXT_LIMIT }))
+        .await
+        .unwrap_or_default();
+    entries
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry["command"].as_str())
+        .filter(|command| !is_own_invocation(command))
+        .map(ToString::to_string)
+        .collect()
+}
+
+/// Returns whether a command runs this program, by name or by path
+fn is_own_invocation(command: &str) -> bool {
+    command
+        .split_whitespace()
+        .next()
+        .and_then(|program| Path::new(program).file_name())
+        .is_some_and(|name| name == PROGRAM_NAME)
+}
+
+/// Determines the failed command from the arguments, the command recorded by
+/// the shell integration or the most recent command of the history. A
+/// recorded invocation of this program is skipped like in the history.
+fn failed_command(
+    command: Option<String>,
+    exit_code: Option<i32>,
+    recorded: Option<LastCommand>,
+    history: &[String],
+) -> Result<FailedCommand, LLMError> {
+    let recorded = recorded.filter(|last| !is_own_invocation(&last.command));
+    let (command, recorded_exit_code, stderr_file) = match command {
+        Some(command) => (command, None, None),
+        None => match recorded {
+            Some(last) => (last.command, last.exit_code, last.stderr_file),
+            None => (
+                history.first().cloned().ok_or_else(|| {
+                    LLMError::ConfigError(format!(
+                        "No previous command found, pass it with --command or load the shell integration with `{PROGRAM_NAME} init <shell>`"
+                    ))
+                })?,
+                None,
+                None,
+            ),
+        },
+    };
+
+    Ok(FailedCommand {
+        command,
+        exit_code: exit_code.or(recorded_exit_code),
+        stderr_file,
+        output: None,
+    })
+}
+
+/// Reads captured output, keeping its beginning and end if it is longer than
+/// `max_bytes`
+fn read_output(path: &Path, max_bytes: usize) -> Result<String, LLMError> {
+    let error =
+        |e: io::Error| LLMError::ConfigError(format!("Failed to read '{}': {e}", path.display()));
+    let mut file = File::open(path).map_err(error)?;
+    let mut output = CappedOutput::new(max_bytes);
+    let mut buffer = [0; 8192];
+    loop {
+        match file.read(&mut buffer).map_err(error)? {
+            0 => return Ok(output.to_string()),
+            read => output.push(&buffer[..read]),
+        }
+    }
+}
+
+/// Returns how to decide about running the failed command again. Running a
+/// command twice is not safe for every command, so even commands allowed by
+/// a rule need confirmation, unless `--yes` is given.
+fn rerun_decision(decision: Decision) -> Decision {
+    match decision {
+        Decision::Allow(Reason::Rule(_)) => Decision::Ask,
+        decision => decision,
+    }
+}
+
+/// Runs the command again to capture its output, as a last resort when no
+/// output was captured before. A command that is not run again is explained
+/// without its output.
+async fn capture_output(
+    failed: &mut FailedCommand,
+    config: &Config,
+    policy: PermissionPolicy,
+) -> Result<(), LLMError> {
+    eprintln!(
+        "No output of '{}' was captured, it has to run again to capture it",
+        failed.command
+    );
+    let decision = rerun_decision(policy.check(&failed.command));
+    if let Decision::Deny(_) = PermissionPolicy::resolve(&failed.command, decision)? {
+        return Ok(());
+    }
+
+    let result = ExecuteCommandTool::new(&config.tools, policy)
+        .run_approved(&failed.command)
+        .await?;
+    if failed.exit_code.is_none() {
+        failed.exit_code = result["exit_code"]
+            .as_i64()
+            .and_then(|code| i32::try_from(code).ok());
+    }
+    failed.output = Some(command_output(&result));
+    Ok(())
+}
+
+/// Joins stdout and stderr of an `execute_command` result
+fn command_output(result: &Value) -> String {
+    ["stdout", "stderr"]
+        .iter()
+        .filter_map(|stream| result[stream].as_str())
+        .filter(|output| !output.trim().is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Builds the request for an explanation and fix of the command
+fn fix_prompt(failed: &FailedCommand, history: &[String]) -> String {
+    let exit_code = failed
+        .exit_code
+        .map_or_else(|| "unknown".to_string(), |code| code.to_string());
+    let mut sections = vec![
+        FIX_INSTRUCTIONS.to_string(),
+        format!("Command:\n{}", fence(&failed.command)),
+        format!("Exit code: {exit_code}"),
+    ];
+    match &failed.output {
+        Some(output) if !output.trim().is_empty() => {
+            sections.push(format!("Output:\n{}", fence(output)));
+        }
+        Some(_) => sections.push("The command printed nothing.".to_string()),
+        None => {}
+    }
+    if let Ok(dir) = std::env::current_dir() {
+        sections.push(format!("Working directory: {}", dir.display()));
+    }
+    let earlier = history
+        .iter()
+        .filter(|command| **command != failed.command)
+        .map(|command| format!("- {}", command.replace('\n', " ")))
+        .collect::<Vec<_>>();
+    if !earlier.is_empty() {
+        sections.push(format!(
+            "Recent commands, most recent first:\n{}",
+            earlier.join("\n")
+        ));
+    }
+    sections.join("\n\n")
+}
+
+/// Extracts the command of the last code block of the response
+fn suggested_command(response: &str) -> Option<String> {
+    let mut suggestion = None;
+    let mut block: Option<(&str, Vec<&str>)> = None;
+    for line in response.lines() {
+        let trimmed = line.trim_start();
+        match &mut block {
+            Some((fence, lines)) => {
+                if trimmed.trim_end() == *fence {
+                    suggestion = Some(lines.join("\n"));
+                    block = None;
+                } else {
+                    lines.push(line);
+                }
+            }
+            None if trimmed.starts_with("```") => {
+                let len = trimmed.len() - trimmed.trim_start_matches('`').len();
+                block = Some((&trimmed[..len], Vec::new()));
+            }
+            None => {}
+        }
+    }
+    suggestion
+        .map(|command| command.trim().to_string())
+        .filter(|command| !command.is_empty())
+}
+
+/// Returns whether the suggested command is run when the user just presses
+/// Enter, which is only the case for commands allowed by a rule. Returns
+/// `None` for commands that must not be offered at all.
+const fn default_answer(decision: &Decision) -> Option<bool> {
+    match decision {
+        Decision::Deny(_) => None,
+        Decision::Allow(Reason::Rule(_)) => Some(true),
+        Decision::Allow(_) | Decision::Ask => Some(false),
+    }
+}
+
+/// Asks whether to run the suggested command. Commands denied by the policy
+/// are refused, and only commands allowed by a rule run on a single Enter.
+/// Without a terminal the suggestion is only printed.
+fn offer_to_run(command: &str, shell: &str, policy: &PermissionPolicy) -> Result<(), LLMError> {
+    if !io::stdin().is_terminal() {
+        return Ok(());
+    }
+    let decision = policy.check(command);
+    let Some(default) = default_answer(&decision) else {
+        if let Decision::Deny(reason) = decision {
+            eprintln!("\nNot running '{command}', it is denied by {reason}");
+        }
+        return Ok(());
+    };
+
+    eprint!(
+        "\nRun '{command}'? {} ",
+        if default { "[Y/n]" } else { "[y/N]" }
+    );
+    io::stderr().flush()?;
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    let run = match input.trim().to_lowercase().as_str() {
+        "" => default,
+        answer => matches!(answer, "y" | "yes"),
+    };
+    if !run {
+        return Ok(());
+    }
+
+    let status = Command::new(shell).arg("-c").arg(command).status()?;
+    if !status.success() {
+        eprintln!("'{command}' failed with {status}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::ApprovalMode;
+
+    #[test]
+    fn test_suggested_command_is_last_code_block() {
+        let response = "`git psuh` is not a git command.\n\n```sh\ngit status\n```\n\nUse:\n\n```sh\ngit push origin main\n```\n";
+        assert_eq!(
+            suggested_command(response),
+            Some("git push origin main".to_string())
+        );
+
+        let nested = "````sh\ncat <<EOF\n```\nEOF\n````";
+        assert_eq!(
+            suggested_command(nested),
+            Some("cat <<EOF\n```\nEOF".to_string())
+        );
+    }
+
+    #[test]
+    fn test_no_suggested_command() {
+        assert_eq!(suggested_command("The disk is full."), None);
+        assert_eq!(suggested_command("```sh\n\n```"), None);
+        assert_eq!(suggested_command("```sh\nnever closed"), None);
+    }
+
+    #[test]
+    fn test_only_allowed_commands_run_by_default() {
+        let config = Config::default();
+        let policy =
+            PermissionPolicy::new(&config.tools.permissions, ApprovalMode::Prompt).unwrap();
+        let answer = |command: &str| default_answer(&policy.check(command));
+
+        assert_eq!(answer("git status"), Some(true));
+        assert_eq!(answer("git push --force"), Some(false));
+        assert_eq!(answer("rm -rf /"), None);
+
+        let yes = PermissionPolicy::new(&config.tools.permissions, ApprovalMode::Yes).unwrap();
+        assert_eq!(default_answer(&yes.check("make")), Some(false));
+    }
+
+    #[test]
+    fn test_running_again_needs_confirmation() {
+        let config = Config::default();
+        let rerun = |mode, command| {
+            let policy = PermissionPolicy::new(&config.tools.permissions, mode).unwrap();
+            rerun_decision(policy.check(command))
+        };
+
+        assert_eq!(rerun(ApprovalMode::Prompt, "git status"), Decision::Ask);
+        assert_eq!(rerun(ApprovalMode::Prompt, "make"), Decision::Ask);
+        assert!(matches!(
+            rerun(ApprovalMode::Prompt, "rm -rf /"),
+            Decision::Deny(_)
+        ));
+        assert!(matches!(
+            rerun(ApprovalMode::Never, "git status"),
+            Decision::Ask
+        ));
+        assert_eq!(
+            rerun(ApprovalMode::Yes, "make"),
+            Decision::Allow(Reason::Mode(ApprovalMode::Yes))
+        );
+    }
+
+    #[test]
+    fn test_read_output_keeps_beginning_and_end() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("stderr");
+        std::fs::write(&path, format!("first\n{}\nlast\n", "x".repeat(1000))).unwrap();
+
+        let output = read_output(&path, 20).unwrap();
+
+        assert!(output.starts_with("first\n"));
+        assert!(output.ends_with("last\n"));
+        assert!(output.contains("bytes truncated"));
+        assert!(matches!(
+            read_output(&dir.path().join("missing"), 20),
+            Err(LLMError::ConfigError(_))
+        ));
+    }
+
+    #[test]
+    fn test_fix_prompt() {
+        let failed = FailedCommand {
+            command: "git psuh".to_string(),
+            exit_code: Some(1),
+            stderr_file: None,
+            output: Some("git: 'psuh' is not a git command.\n".to_string()),
+        };
+        let history = ["git psuh".to_string(), "git commit -m 'wip'".to_string()];
+
+        let prompt = fix_prompt(&failed, &history);
+
+        assert!(prompt.starts_with(FIX_INSTRUCTIONS));
+        assert!(prompt.contains("Command:\n```\ngit psuh\n```\n\nExit code: 1\n\n"));
+        assert!(prompt.contains("Output:\n```\ngit: 'psuh' is not a git command.\n```"));
+        assert!(prompt.ends_with("most recent first:\n- git commit -m 'wip'"));
+    }
+
+    #[test]
+    fn test_command_from_arguments() {
+        let history = ["make".to_string()];
+
+        let given =
+            failed_command(Some("cargo bild".to_string()), Some(101), None, &history).unwrap();
+        assert_eq!(given.command, "cargo bild");
+        assert_eq!(given.exit_code, Some(101));
+
+        assert!(is_own_invocation("llm-cli fix --exit-code 1"));
+        assert!(is_own_invocation("/usr/local/bin/llm-cli fix"));
+        assert!(!is_own_invocation("cargo run -- llm-cli"));
+    }
+
+    #[test]
+    fn test_recorded_command_skips_own_invocations() {
+        let history = ["make".to_string()];
+        let recorded = |command: &str| {
+            Some(LastCommand {
+                command: command.to_string(),
+                exit_code: Some(2),
+                stderr_file: Some(PathBuf::from("/run/user/1000/llm-cli/stderr.abc123")),
+            })
+        };
+
+        let failed = failed_command(None, None, recorded("cargo bild"), &history).unwrap();
+        assert_eq!(failed.command, "cargo bild");
+        assert_eq!(failed.exit_code, Some(2));
+        assert!(failed.stderr_file.is_some());
+
+        let own = failed_command(None, None, recorded("llm-cli fix"), &history).unwrap();
+        assert_eq!(own.command, "make");
+        assert_eq!(own.exit_code, None);
+        assert_eq!(own.stderr_file, None);
+
+        let given = failed_command(
+            Some("make".to_string()),
+            None,
+            recorded("cargo bild"),
+            &history,
+        )
+        .unwrap();
+        assert_eq!(given.stderr_file, None);
+    }
+}
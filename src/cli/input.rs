@@ -0,0 +1,112 @@
+use std::io::{self, IsTerminal, Read};
+
+use crate::core::LLMError;
+
+/// Query argument that means "read the query from stdin"
+const STDIN_ARG: &str = "-";
+/// Minimum length of the backtick fence wrapped around piped context
+const MIN_FENCE_LEN: usize = 3;
+
+/// Returns whether stdin is connected to an interactive terminal
+pub fn stdin_is_terminal() -> bool {
+    io::stdin().is_terminal()
+}
+
+/// Resolves the user prompt from the query argument and piped stdin.
+///
+/// Stdin is read when the query is `-` or when input is piped into the
+/// process. Returns `None` if neither provides any content.
+pub fn read_query(query: Option<String>) -> Result<Option<String>, LLMError> {
+    let piped = if query.as_deref() == Some(STDIN_ARG) || !stdin_is_terminal() {
+        let mut input = String::new();
+        io::stdin().read_to_string(&mut input)?;
+        Some(input)
+    } else {
+        None
+    };
+
+    Ok(compose_prompt(query, piped))
+}
+
+/// Combines the query with piped input.
+///
+/// A query of `-` is replaced by the piped input; any other query gets the
+/// piped input attached as a fenced context block.
+fn compose_prompt(query: Option<String>, piped: Option<String>) -> Option<String> {
+    let piped = piped.filter(|input| !input.trim().is_empty());
+
+    match (query, piped) {
+        (Some(query), piped) if query == STDIN_ARG => piped,
+        (Some(query), Some(context)) => Some(format!("{query}\n\n{}", fence(&context))),
+        (query, None) => query,
+        (None, Some(context)) => Some(context),
+    }
+}
+
+/// Wraps content in a code fence that is longer than any backtick run inside it
+pub(super) fn fence(content: &str) -> String {
+    let longest_run = content
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or_default();
+    let fence = "`".repeat((longest_run + 1).max(MIN_FENCE_LEN));
+    let content = content.trim_end_matches('\n');
+
+    format!("{fence}\n{content}\n{fence}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_without_stdin() {
+        assert_eq!(
+            compose_prompt(Some("hello".to_string()), None),
+            Some("hello".to_string())
+        );
+        assert_eq!(compose_prompt(None, None), None);
+    }
+
+    #[test]
+    fn test_stdin_as_prompt() {
+        assert_eq!(
+            compose_prompt(None, Some("explain this\n".to_string())),
+            Some("explain this\n".to_string())
+        );
+        assert_eq!(
+            compose_prompt(Some("-".to_string()), Some("from stdin".to_string())),
+            Some("from stdin".to_string())
+        );
+        assert_eq!(
+            compose_prompt(Some("-".to_string()), Some("  \n".to_string())),
+            None
+        );
+    }
+
+    #[test]
+    fn test_stdin_as_context() {
+        assert_eq!(
+            compose_prompt(
+                Some("explain this".to_string()),
+                Some("error: boom\n".to_string())
+            ),
+            Some("explain this\n\n```\nerror: boom\n```".to_string())
+        );
+    }
+
+    #[test]
+    fn test_empty_stdin_is_ignored() {
+        assert_eq!(
+            compose_prompt(Some("hello".to_string()), Some(String::new())),
+            Some("hello".to_string())
+        );
+    }
+
+    #[test]
+    fn test_fence_longer_than_content_backticks() {
+        assert_eq!(fence("a ```rust``` b"), "````\na ```rust``` b\n````");
+        assert_eq!(fence("no ticks"), "```\nno ticks\n```");
+    }
+}
@@ -1,5 +1,11 @@
 mod args;
+mod fix;
+mod input;
+mod repl;
 mod run;
+mod shell;
+mod suggest;
 
-pub use args::Args;
+pub use args::{Args, Command, FixArgs};
 pub use run::run;
+pub use shell::Shell;
@@ -0,0 +1,313 @@
+use std::io::{self, BufRead, Write};
+
+use clap::ValueEnum;
+
+use super::run::{create_llm_client, create_tool_registry, record_usage, save_session, RunOptions};
+use crate::{
+    core::{conversation::ConversationManager, Config, LLMError, Provider, Session, SessionStore},
+    providers::Message,
+    tools::process,
+};
+
+const PROMPT: &str = "> ";
+const HELP_TEXT: &str = "Commands:
+  /reset             Clear the conversation history and start a new session
+  /profile [name]    Show or switch the configuration profile
+  /provider [name]   Switch to a profile of the provider (claude, openai, openai-compatible)
+  /model [name]      Show or switch the model of the current profile
+  /help              Show this help
+  /exit              Leave the chat";
+
+/// Slash commands understood by the interactive chat
+#[derive(Debug, PartialEq, Eq)]
+enum ReplCommand {
+    Reset,
+    Profile(Option<String>),
+    Provider(Option<String>),
+    Model(Option<String>),
+    Help,
+    Exit,
+    Unknown(String),
+}
+
+impl ReplCommand {
+    /// Parses a line of user input into a slash command.
+    ///
+    /// Returns `None` if the input is not a slash command.
+    fn parse(input: &str) -> Option<Self> {
+        let input = input.strip_prefix('/')?;
+        let mut parts = input.split_whitespace();
+        let name = parts.next().unwrap_or_default();
+        let argument = parts.next().map(String::from);
+
+        let command = match name {
+            "reset" | "clear" => Self::Reset,
+            "profile" => Self::Profile(argument),
+            "provider" => Self::Provider(argument),
+            "model" => Self::Model(argument),
+            "help" | "?" => Self::Help,
+            "exit" | "quit" | "q" => Self::Exit,
+            _ => Self::Unknown(name.to_string()),
+        };
+        Some(command)
+    }
+}
+
+/// Interactive multi-turn chat that keeps the message history between turns
+/// and saves it to the session store after every turn
+pub struct Repl {
+    config: Config,
+    conversation_manager: ConversationManager,
+    store: SessionStore,
+    session: Session,
+    options: RunOptions,
+}
+
+impl Repl {
+    /// Creates a new interactive chat session.
+    ///
+    /// # Arguments
+    /// * `config` - The configuration used to (re)create LLM clients
+    /// * `conversation_manager` - The conversation manager driving each turn
+    /// * `store` - The store the session is saved to after every turn
+    /// * `session` - The session to continue, possibly without any messages yet
+    /// * `options` - Command line settings taking precedence over the profile
+    pub(super) const fn new(
+        config: Config,
+        conversation_manager: ConversationManager,
+        store: SessionStore,
+        session: Session,
+        options: RunOptions,
+    ) -> Self {
+        Self {
+            config,
+            conversation_manager,
+            store,
+            session,
+            options,
+        }
+    }
+
+    /// Runs the chat loop until the user exits or stdin is closed.
+    ///
+    /// # Arguments
+    /// * `initial_query` - Optional first message to send before prompting
+    pub async fn run(&mut self, initial_query: Option<String>) -> Result<(), LLMError> {
+        let mut stdout = io::stdout();
+        let stdin = io::stdin();
+
+        if !self.session.messages.is_empty() {
+            eprintln!(
+                "Resumed session '{}' ({} messages)",
+                self.session.name,
+                self.session.messages.len()
+            );
+        }
+
+        if let Some(query) = initial_query.filter(|query| !query.is_empty()) {
+            self.send(query, &mut stdout).await?;
+        }
+
+        loop {
+            write!(stdout, "{PROMPT}")?;
+            stdout.flush()?;
+
+            let mut line = String::new();
+            if stdin.lock().read_line(&mut line)? == 0 {
+                writeln!(stdout)?;
+                break;
+            }
+
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            match ReplCommand::parse(line) {
+                Some(ReplCommand::Exit) => break,
+                Some(command) => self.handle_command(command, &mut stdout).await?,
+                None => self.send(line.to_string(), &mut stdout).await?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sends a user message, appends the resulting messages to the history and
+    /// saves the session.
+    ///
+    /// Errors from the provider are reported without ending the chat, and the
+    /// failed user message is dropped from the history. Ctrl-C cancels the
+    /// turn the same way.
+    async fn send<W: Write + Send>(
+        &mut self,
+        query: String,
+        writer: &mut W,
+    ) -> Result<(), LLMError> {
+        self.session.messages.push(Message::user(query));
+
+        let result = process::interruptible(self.conversation_manager.run(
+            self.session.messages.clone(),
+            self.options.max_steps(&self.config),
+            writer,
+        ))
+        .await;
+        let Some(result) = result else {
+            self.session.messages.pop();
+            self.conversation_manager.finish_output(writer)?;
+            writeln!(writer)?;
+            eprintln!("Interrupted");
+            writeln!(writer)?;
+            return Ok(());
+        };
+
+        match result {
+            Ok(messages) => {
+                self.session.messages = messages;
+                writeln!(writer)?;
+                record_usage(
+                    &mut self.session,
+                    self.conversation_manager.usage(),
+                    &self.config,
+                    self.options.show_usage,
+                );
+                save_session(&self.store, &mut self.session, &self.config);
+            }
+            Err(e) => {
+                self.session.messages.pop();
+                writeln!(writer)?;
+                eprintln!("Error: {e}");
+                writeln!(writer)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Executes a slash command.
+    async fn handle_command<W: Write + Send>(
+        &mut self,
+        command: ReplCommand,
+        writer: &mut W,
+    ) -> Result<(), LLMError> {
+        match command {
+            ReplCommand::Reset => {
+                self.session = Session::new(None, &self.config);
+                writeln!(
+                    writer,
+                    "Conversation history cleared, started session '{}'",
+                    self.session.name
+                )?;
+            }
+            ReplCommand::Profile(None) => {
+                writeln!(
+                    writer,
+                    "Current profile: {} (available: {})",
+                    self.config.profile,
+                    self.config.profile_names().join(", ")
+                )?;
+            }
+            ReplCommand::Profile(Some(name)) => match self.config.update_profile(&name) {
+                Ok(()) => self.reload_client(writer).await?,
+                Err(e) => writeln!(writer, "{e}")?,
+            },
+            ReplCommand::Provider(None) => {
+                writeln!(
+                    writer,
+                    "Current provider: {}",
+                    self.config.get_provider().name()
+                )?;
+            }
+            ReplCommand::Provider(Some(name)) => match Provider::from_str(&name, true) {
+                Ok(provider) => match self.config.update_provider(provider) {
+                    Ok(()) => self.reload_client(writer).await?,
+                    Err(e) => writeln!(writer, "{e}")?,
+                },
+                Err(_) => writeln!(writer, "Unknown provider: {name}")?,
+            },
+            ReplCommand::Model(None) => {
+                writeln!(writer, "Current model: {}", self.config.get_model())?;
+            }
+            ReplCommand::Model(Some(model)) => {
+                self.config.update_model(model);
+                self.reload_client(writer).await?;
+            }
+            ReplCommand::Help => writeln!(writer, "{HELP_TEXT}")?,
+            ReplCommand::Unknown(name) => {
+                writeln!(writer, "Unknown command: /{name} (type /help for a list)")?;
+            }
+            ReplCommand::Exit => {}
+        }
+        Ok(())
+    }
+
+    /// Recreates the LLM client and tools after the profile or model changed.
+    async fn reload_client<W: Write + Send>(&mut self, writer: &mut W) -> Result<(), LLMError> {
+        let reloaded = match create_llm_client(self.config.clone(), self.options.debug) {
+            Ok(client) => create_tool_registry(&self.config, &self.options)
+                .await
+                .map(|registry| (client, registry)),
+            Err(e) => Err(e),
+        };
+        match reloaded {
+            Ok((client, registry)) => {
+                self.conversation_manager.set_client(client);
+                self.conversation_manager.set_tool_registry(registry);
+                writeln!(
+                    writer,
+                    "Using profile {} ({}) with model {}",
+                    self.config.profile,
+                    self.config.get_provider().name(),
+                    self.config.get_model()
+                )?;
+            }
+            Err(e) => eprintln!("Error: {e}"),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_non_command() {
+        assert_eq!(ReplCommand::parse("hello /reset"), None);
+        assert_eq!(ReplCommand::parse("what is /etc/hosts?"), None);
+    }
+
+    #[test]
+    fn test_parse_commands() {
+        let cases = [
+            ("/reset", ReplCommand::Reset),
+            ("/exit", ReplCommand::Exit),
+            ("/quit", ReplCommand::Exit),
+            ("/help", ReplCommand::Help),
+            ("/profile", ReplCommand::Profile(None)),
+            (
+                "/profile fast",
+                ReplCommand::Profile(Some("fast".to_string())),
+            ),
+            ("/provider", ReplCommand::Provider(None)),
+            (
+                "/provider openai",
+                ReplCommand::Provider(Some("openai".to_string())),
+            ),
+            ("/model", ReplCommand::Model(None)),
+            (
+                "/model  gpt-4o-mini ",
+                ReplCommand::Model(Some("gpt-4o-mini".to_string())),
+            ),
+            ("/unknown", ReplCommand::Unknown("unknown".to_string())),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(
+                ReplCommand::parse(input),
+                Some(expected),
+                "Failed on input: {input}"
+            );
+        }
+    }
+}
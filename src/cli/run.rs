@@ -1,86 +1,406 @@
 use log::debug;
 
-use super::args::Args;
+use super::{
+    args::{Args, Command},
+    fix, input,
+    repl::Repl,
+    suggest,
+};
 use crate::{
-    core::{conversation::ConversationManager, Config, Formatter, LLMError, Provider},
-    providers::{claude::ClaudeClient, llm::LLMClient, openai::OpenAIClient, Message},
-    tools::{CommandHistoryTool, ExecuteCommandTool, ToolRegistry},
+    core::{
+        conversation::ConversationManager, response_json, Config, Formatter, LLMError,
+        OutputFormat, Provider, Session, SessionStore,
+    },
+    providers::{claude::ClaudeClient, llm::LLMClient, openai::OpenAIClient, Message, Usage},
+    tools::{
+        mcp, process, ApprovalMode, CommandHistoryTool, CustomTool, EditFileTool,
+        ExecuteCommandTool, ListFilesTool, PermissionPolicy, ReadFileTool, SearchFilesTool,
+        ToolRegistry, Workspace, WriteFileTool,
+    },
 };
 use std::io::{self, Write};
+use std::time::Duration;
+
+/// Reads an API key from `.env` or the environment
+fn read_api_key(var: &str) -> Result<String, LLMError> {
+    dotenv::var(var)
+        .or_else(|_| std::env::var(var))
+        .map_err(|_| LLMError::ApiError(format!("{var} not set in .env or environment")))
+}
+
+/// Command line settings that take precedence over the active profile
+#[derive(Debug, Clone, Copy)]
+pub(super) struct RunOptions {
+    pub enable_tools: Option<bool>,
+    pub max_steps: Option<u32>,
+    pub show_usage: bool,
+    /// How commands without a matching permission rule are approved
+    pub approval: ApprovalMode,
+    pub debug: bool,
+}
+
+impl RunOptions {
+    const fn from_args(args: &Args) -> Self {
+        Self {
+            enable_tools: args.enable_tools,
+            max_steps: args.max_steps,
+            show_usage: args.usage,
+            approval: args.approval_mode(),
+            debug: args.debug,
+        }
+    }
+
+    /// Whether tools are enabled, from the command line or the active profile
+    pub fn enable_tools(&self, config: &Config) -> bool {
+        self.enable_tools
+            .unwrap_or_else(|| config.get_enable_tools())
+    }
 
-/// Creates a new LLM client based on the specified provider
+    /// Maximum number of tool steps, from the command line or the active profile
+    pub fn max_steps(&self, config: &Config) -> u32 {
+        self.max_steps.unwrap_or_else(|| config.get_max_steps())
+    }
+}
+
+/// Creates a new LLM client based on the backend of the active profile
 ///
 /// # Arguments
-/// * `provider` - The name of the LLM provider to use
-/// * `config` - The configuration for the client
+/// * `config` - The configuration for the client, including the active profile
 /// * `debug` - Whether to output debug information
 ///
 /// # Returns
 /// A boxed LLM client implementing the `LLMClient` trait
-fn create_llm_client(config: Config, debug: bool) -> Result<Box<dyn LLMClient>, LLMError> {
-    match config.provider {
+pub(super) fn create_llm_client(
+    config: Config,
+    debug: bool,
+) -> Result<Box<dyn LLMClient>, LLMError> {
+    match config.get_provider() {
         Provider::Claude => {
             if debug {
                 eprintln!("[DEBUG] Initializing Claude client");
             }
-            let api_key = dotenv::var("ANTHROPIC_API_KEY")
-                .or_else(|_| std::env::var("ANTHROPIC_API_KEY"))
-                .map_err(|_| {
-                    LLMError::ApiError(
-                        "ANTHROPIC_API_KEY not set in .env or environment".to_string(),
-                    )
-                })?;
+            let api_key = read_api_key("ANTHROPIC_API_KEY")?;
             Ok(Box::new(ClaudeClient::new(api_key, config)))
         }
         Provider::OpenAI => {
             if debug {
                 eprintln!("[DEBUG] Initializing OpenAI client");
             }
-            let api_key = dotenv::var("OPENAI_API_KEY")
-                .or_else(|_| std::env::var("OPENAI_API_KEY"))
-                .map_err(|_| {
-                    LLMError::ApiError("OPENAI_API_KEY not set in .env or environment".to_string())
-                })?;
-            Ok(Box::new(OpenAIClient::new(api_key, config)))
+            let profile = config.active_profile().clone();
+            let api_key = read_api_key(profile.api_key_env.as_deref().unwrap_or("OPENAI_API_KEY"))?;
+            let mut client = OpenAIClient::new(api_key, config).with_headers(&profile.headers)?;
+            if let Some(base_url) = profile.base_url {
+                client = client.with_base_url(base_url);
+            }
+            Ok(Box::new(client))
+        }
+        Provider::OpenAICompatible => {
+            let profile = config.active_profile().clone();
+            let base_url = profile.base_url.ok_or_else(|| {
+                LLMError::ConfigError(format!(
+                    "Profile '{}' uses the openai-compatible backend but has no base_url",
+                    config.profile
+                ))
+            })?;
+            if debug {
+                eprintln!("[DEBUG] Initializing OpenAI-compatible client for {base_url}");
+            }
+            let api_key = profile
+                .api_key_env
+                .as_deref()
+                .map(read_api_key)
+                .transpose()?
+                .unwrap_or_default();
+            let client = OpenAIClient::new(api_key, config)
+                .with_base_url(base_url)
+                .with_headers(&profile.headers)?;
+            Ok(Box::new(client))
         }
     }
 }
 
-pub async fn run(args: Args) -> Result<(), LLMError> {
-    let _ = dotenv::dotenv();
+/// Creates the tool registry with all built-in tools and the tools of the
+/// configured MCP servers, or `None` if tools are disabled
+///
+/// # Errors
+/// Returns a configuration error if a permission rule, the workspace root or a
+/// custom tool is invalid
+pub(super) async fn create_tool_registry(
+    config: &Config,
+    options: &RunOptions,
+) -> Result<Option<ToolRegistry>, LLMError> {
+    if !options.enable_tools(config) {
+        return Ok(None);
+    }
 
-    let query = args.query;
-    if query.is_empty() {
-        return Err(LLMError::ApiError("Query must not be empty".to_string()));
+    let policy = PermissionPolicy::new(&config.tools.permissions, options.approval)?;
+    let workspace = Workspace::from_config(&config.tools)?;
+    let mut registry = ToolRegistry::new();
+    registry.register(ExecuteCommandTool::new(&config.tools, policy.clone()));
+    registry.register(CommandHistoryTool);
+    registry.register(ReadFileTool::new(
+        workspace.clone(),
+        config.tools.max_read_bytes,
+        policy.clone(),
+    ));
+    registry.register(ListFilesTool::new(
+        workspace.clone(),
+        config.tools.max_search_results,
+    ));
+    registry.register(SearchFilesTool::new(
+        workspace.clone(),
+        config.tools.max_search_results,
+        config.tools.max_search_file_bytes,
+    ));
+    registry.register(EditFileTool::new(workspace.clone(), policy.clone()));
+    registry.register(WriteFileTool::new(workspace, policy.clone()));
+    for tool in &config.tools.custom {
+        if registry.get_tool(&tool.name).is_some() {
+            return Err(LLMError::ConfigError(format!(
+                "Custom tool '{}' conflicts with another tool of the same name",
+                tool.name
+            )));
+        }
+        registry.register(CustomTool::new(tool, &config.tools, policy.clone())?);
     }
-    let mut config = Config::load()?;
-    let enable_tools = args.enable_tools.unwrap_or(config.enable_tools);
-    let max_steps = args.max_steps.unwrap_or(config.max_steps);
+    mcp::register_servers(
+        &mut registry,
+        &config.mcp_servers,
+        &policy,
+        Duration::from_secs(config.tools.timeout_secs),
+        options.debug,
+    )
+    .await;
+    Ok(Some(registry))
+}
+
+/// Reads the system prompt override from `--system` or `--system-file`, if any.
+fn system_prompt_override(args: &Args) -> Result<Option<String>, LLMError> {
+    args.system_file.as_ref().map_or_else(
+        || Ok(args.system.clone()),
+        |path| {
+            std::fs::read_to_string(path).map(Some).map_err(|e| {
+                LLMError::ConfigError(format!(
+                    "Failed to read system prompt file '{}': {e}",
+                    path.display()
+                ))
+            })
+        },
+    )
+}
 
+/// Applies the profile, provider and system prompt chosen on the command line
+fn apply_overrides(config: &mut Config, args: &Args) -> Result<(), LLMError> {
+    if let Some(profile) = &args.profile {
+        config.update_profile(profile)?;
+    }
     if let Some(provider) = args.provider {
-        config.update_provider(provider);
+        config.update_provider(provider)?;
+    }
+    if let Some(system_prompt) = system_prompt_override(args)? {
+        config.update_system_prompt(system_prompt);
     }
+    Ok(())
+}
+
+/// Loads the session requested through `--session` or `--continue`, if any.
+fn load_session(args: &Args, store: &SessionStore) -> Result<Option<Session>, LLMError> {
+    if let Some(name) = &args.session {
+        store.load(name)
+    } else if args.continue_session {
+        let session = store.load_last()?;
+        if session.is_none() {
+            eprintln!("No previous session found, starting a new one");
+        }
+        Ok(session)
+    } else {
+        Ok(None)
+    }
+}
+
+/// Restores the profile and model a resumed session was using.
+///
+/// Sessions whose profile no longer exists fall back to a profile with the same provider.
+fn apply_session(config: &mut Config, session: &Session) -> Result<(), LLMError> {
+    match &session.profile {
+        Some(profile) if config.profiles.contains_key(profile) => config.update_profile(profile)?,
+        _ => config.update_provider(session.provider)?,
+    }
+    config.update_model(session.model.clone());
+    Ok(())
+}
+
+/// Adds the usage of a turn to the session totals and prints the usage footer
+/// to stderr if requested.
+///
+/// The cost is estimated from the price of the current model, turns with an
+/// unknown price are left out of the cost.
+pub(super) fn record_usage(session: &mut Session, usage: Usage, config: &Config, show: bool) {
+    let cost = usage_cost(&usage, config);
+    let first_turn = session.usage.is_empty();
+    session.usage += usage;
+    session.cost += cost.unwrap_or_default();
+
+    if show {
+        let session_total = if first_turn {
+            String::new()
+        } else {
+            let session_cost = cost.map(|_| session.cost);
+            format!(" (session: {})", format_usage(&session.usage, session_cost))
+        };
+        eprintln!("Usage: {}{session_total}", format_usage(&usage, cost));
+    }
+}
+
+/// Prints the usage footer of a run that is not part of a session to stderr
+pub(super) fn print_usage(usage: &Usage, config: &Config) {
+    eprintln!("Usage: {}", format_usage(usage, usage_cost(usage, config)));
+}
+
+/// Estimates the cost of the usage from the price of the current model
+fn usage_cost(usage: &Usage, config: &Config) -> Option<f64> {
+    config
+        .get_price(config.get_model())
+        .map(|price| price.cost(usage))
+}
+
+/// Formats token counts and the estimated cost for the usage footer
+fn format_usage(usage: &Usage, cost: Option<f64>) -> String {
+    let cache = if usage.cache_read_tokens > 0 || usage.cache_creation_tokens > 0 {
+        format!(
+            ", {} cached, {} cache write",
+            usage.cache_read_tokens, usage.cache_creation_tokens
+        )
+    } else {
+        String::new()
+    };
+    let cost = cost
+        .map(|cost| format!(", ~${cost:.4}"))
+        .unwrap_or_default();
+
+    format!(
+        "{} input, {} output{cache} tokens{cost}",
+        usage.input_tokens, usage.output_tokens
+    )
+}
+
+/// Saves the session with the profile and model currently in use.
+///
+/// Failing to save is reported as a warning since the conversation itself succeeded.
+pub(super) fn save_session(store: &SessionStore, session: &mut Session, config: &Config) {
+    session.profile = Some(config.profile.clone());
+    session.provider = config.get_provider();
+    session.model = config.get_model().to_string();
+    if let Err(e) = store.save(session) {
+        eprintln!("Warning: failed to save session '{}': {e}", session.name);
+    }
+}
+
+pub async fn run(mut args: Args) -> Result<(), LLMError> {
+    let _ = dotenv::dotenv();
+    process::exit_on_interrupt();
+
+    match args.command.take() {
+        Some(Command::Init { shell }) => {
+            print!("{}", shell.init_script());
+            return Ok(());
+        }
+        Some(Command::Fix(fix_args)) => {
+            let mut config = Config::load(args.config.as_deref())?;
+            apply_overrides(&mut config, &args)?;
+            return fix::run(config, RunOptions::from_args(&args), fix_args).await;
+        }
+        Some(Command::Suggest { description }) => {
+            let mut config = Config::load(args.config.as_deref())?;
+            apply_overrides(&mut config, &args)?;
+            return suggest::run(config, args.debug, &description.join(" ")).await;
+        }
+        None => {}
+    }
+
+    let stdin_is_terminal = input::stdin_is_terminal();
+    let query = input::read_query(args.query.take())?;
+    let interactive = args.chat || (query.is_none() && stdin_is_terminal);
+    if !interactive && query.as_deref().is_none_or(str::is_empty) {
+        return Err(LLMError::ApiError("Query must not be empty".to_string()));
+    }
+    if interactive && args.output.is_machine_readable() {
+        return Err(LLMError::ConfigError(
+            "--output json and jsonl are not supported in interactive chats".to_string(),
+        ));
+    }
+    let mut config = Config::load(args.config.as_deref())?;
+
+    let store = SessionStore::open_default()?;
+    let mut session = match load_session(&args, &store)? {
+        Some(session) => {
+            debug!(
+                "[SESSION] resuming '{}' with {} messages",
+                session.name,
+                session.messages.len()
+            );
+            apply_session(&mut config, &session)?;
+            session
+        }
+        None => Session::new(args.session.clone(), &config),
+    };
+
+    apply_overrides(&mut config, &args)?;
+
+    let options = RunOptions::from_args(&args);
+    let enable_tools = options.enable_tools(&config);
+    let max_steps = options.max_steps(&config);
 
     debug!(
-        "[SETTINGS] provider: {:?}, tool_enabled: {enable_tools}, max_steps: {max_steps}",
-        config.provider
+        "[SETTINGS] profile: {}, provider: {:?}, model: {}, tool_enabled: {enable_tools}, max_steps: {max_steps}, interactive: {interactive}",
+        config.profile,
+        config.get_provider(),
+        config.get_model()
     );
 
     let formatter = Formatter::new(std::mem::take(&mut config.theme));
-    let client = create_llm_client(config, args.debug)?;
-    let registry = enable_tools.then(|| {
-        let mut registry = ToolRegistry::new();
-        registry.register(ExecuteCommandTool);
-        registry.register(CommandHistoryTool);
-        registry
-    });
-    let mut conversation_manager = ConversationManager::new(client, registry, formatter);
+    let client = create_llm_client(config.clone(), args.debug)?;
+    let registry = create_tool_registry(&config, &options).await?;
+    let mut conversation_manager =
+        ConversationManager::new(client, registry, formatter).with_output(args.output);
+
+    if interactive {
+        return Repl::new(config, conversation_manager, store, session, options)
+            .run(query)
+            .await;
+    }
+
+    let query = query.unwrap_or_default();
     let mut stdout = io::stdout();
-    let _ = conversation_manager
-        .run(vec![Message::user(query)], max_steps, &mut stdout)
+    session.messages.push(Message::user(query));
+    let first_response = session.messages.len();
+    session.messages = conversation_manager
+        .run(
+            std::mem::take(&mut session.messages),
+            max_steps,
+            &mut stdout,
+        )
         .await?;
+    match args.output {
+        // Finish the response line before the footer
+        OutputFormat::Text | OutputFormat::Raw => writeln!(&mut stdout)?,
+        OutputFormat::Json => {
+            let response = response_json(
+                &session.messages[first_response..],
+                config.get_model(),
+                conversation_manager.usage(),
+            );
+            writeln!(&mut stdout, "{response}")?;
+        }
+        OutputFormat::Jsonl => {}
+    }
+    record_usage(
+        &mut session,
+        conversation_manager.usage(),
+        &config,
+        options.show_usage,
+    );
+    save_session(&store, &mut session, &config);
 
-    // Ensure final newline
-    writeln!(&mut stdout)?;
     Ok(())
 }
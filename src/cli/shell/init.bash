@@ -0,0 +1,85 @@
+# llm-cli integration for bash, load it from ~/.bashrc with:
+#   eval "$(llm-cli init bash)"
+
+# Records the last command and its exit code for `llm-cli fix`, in a file
+# created by mktemp in a directory only the user can access. With
+# LLM_CLI_CAPTURE_STDERR=1 set before loading, the error output of the command
+# is also copied to a second file there, unless a DEBUG trap is already set.
+__llm_cli_dir="${XDG_RUNTIME_DIR:-${XDG_CACHE_HOME:-$HOME/.cache}}/llm-cli"
+if mkdir -p -m 700 "$__llm_cli_dir" 2>/dev/null &&
+  LLM_CLI_LAST_COMMAND_FILE=$(mktemp "$__llm_cli_dir/last-command.XXXXXX" 2>/dev/null); then
+  export LLM_CLI_LAST_COMMAND_FILE
+  if [[ $LLM_CLI_CAPTURE_STDERR == 1 && -z $(trap -p DEBUG) ]] &&
+    LLM_CLI_STDERR_FILE=$(mktemp "$__llm_cli_dir/stderr.XXXXXX" 2>/dev/null); then
+    export LLM_CLI_STDERR_FILE
+  else
+    unset LLM_CLI_STDERR_FILE
+  fi
+  [[ -n $(trap -p EXIT) ]] ||
+    trap 'rm -f -- "$LLM_CLI_LAST_COMMAND_FILE" ${LLM_CLI_STDERR_FILE:+"$LLM_CLI_STDERR_FILE"}' EXIT
+else
+  unset LLM_CLI_LAST_COMMAND_FILE LLM_CLI_STDERR_FILE
+fi
+unset __llm_cli_dir
+
+# Invocations of llm-cli itself, which are not recorded
+__llm_cli_own_command='^[[:space:]]*([^[:space:]]*/)?llm-cli([[:space:]]|$)'
+
+# Number of the last recorded history entry, so that an empty command line
+# does not record the previous command again
+[[ $(HISTTIMEFORMAT= builtin history 1) =~ ^\ *([0-9]+) ]] && __llm_cli_history_number=${BASH_REMATCH[1]}
+
+# Sends the error output of a new command line through tee into
+# LLM_CLI_STDERR_FILE. Runs as DEBUG trap, which only acts on the first
+# command after the prompt.
+__llm_cli_preexec() {
+  [[ -n $__llm_cli_at_prompt ]] || return 0
+  unset __llm_cli_at_prompt
+  if [[ $(HISTTIMEFORMAT= builtin history 1) =~ ^\ *([0-9]+)\*?\ +(.*)$ ]] &&
+    [[ ${BASH_REMATCH[1]} != "$__llm_cli_history_number" ]] &&
+    ! [[ ${BASH_REMATCH[2]} =~ $__llm_cli_own_command ]]; then
+    exec {__llm_cli_stderr}>&2 2> >(tee -i -- "$LLM_CLI_STDERR_FILE" >&2)
+  fi
+  return 0
+}
+
+__llm_cli_precmd() {
+  local exit_code=$?
+  if [[ -n $__llm_cli_stderr ]]; then
+    exec 2>&"$__llm_cli_stderr" {__llm_cli_stderr}>&-
+    unset __llm_cli_stderr
+  fi
+  if [[ $(HISTTIMEFORMAT= builtin history 1) =~ ^\ *([0-9]+)\*?\ +(.*)$ ]] &&
+    [[ ${BASH_REMATCH[1]} != "$__llm_cli_history_number" ]]; then
+    __llm_cli_history_number=${BASH_REMATCH[1]}
+    local command=${BASH_REMATCH[2]}
+    # Running `llm-cli fix` must not replace the command it is about to fix
+    if [[ -n $LLM_CLI_LAST_COMMAND_FILE ]] && ! [[ $command =~ $__llm_cli_own_command ]]; then
+      printf '%s\n%s\n' "$exit_code" "$command" >| "$LLM_CLI_LAST_COMMAND_FILE"
+    fi
+  fi
+  [[ -z $LLM_CLI_STDERR_FILE ]] || __llm_cli_at_prompt=1
+  return "$exit_code"
+}
+
+if [[ $PROMPT_COMMAND != *__llm_cli_precmd* ]]; then
+  PROMPT_COMMAND="__llm_cli_precmd${PROMPT_COMMAND:+;$PROMPT_COMMAND}"
+fi
+[[ -z $LLM_CLI_STDERR_FILE ]] || trap '__llm_cli_preexec' DEBUG
+
+# Replaces the command line with the command suggested for it by
+# `llm-cli suggest`, bound to Ctrl-X Ctrl-L
+__llm_cli_suggest() {
+  [[ -n $READLINE_LINE ]] || return 0
+  local suggestion
+  if suggestion=$(llm-cli suggest -- "$READLINE_LINE" </dev/null 2>/dev/null) && [[ -n $suggestion ]]; then
+    READLINE_LINE=$suggestion
+    READLINE_POINT=${#READLINE_LINE}
+  else
+    echo "llm-cli suggest failed, run it directly to see why" >&2
+  fi
+}
+
+if [[ $- == *i* ]]; then
+  bind -x '"\C-x\C-l": __llm_cli_suggest'
+fi
@@ -0,0 +1,47 @@
+# llm-cli integration for fish, load it from ~/.config/fish/config.fish with:
+#   llm-cli init fish | source
+
+# Records the last command and its exit code for `llm-cli fix`, in a file
+# created by mktemp in a directory only the user can access
+set -l __llm_cli_dir $HOME/.cache
+set -q XDG_CACHE_HOME; and set __llm_cli_dir $XDG_CACHE_HOME
+set -q XDG_RUNTIME_DIR; and set __llm_cli_dir $XDG_RUNTIME_DIR
+set -l __llm_cli_file
+mkdir -p -m 700 $__llm_cli_dir/llm-cli 2>/dev/null
+and set __llm_cli_file (mktemp $__llm_cli_dir/llm-cli/last-command.XXXXXX 2>/dev/null)
+if test -n "$__llm_cli_file"
+    set -gx LLM_CLI_LAST_COMMAND_FILE $__llm_cli_file
+else
+    set -e LLM_CLI_LAST_COMMAND_FILE
+end
+# Fish cannot redirect its own error output, so LLM_CLI_CAPTURE_STDERR is not
+# supported and `llm-cli fix` runs the command again to capture it
+set -e LLM_CLI_STDERR_FILE
+
+function __llm_cli_postexec --on-event fish_postexec
+    set -l exit_code $status
+    test -n "$argv[1]" -a -n "$LLM_CLI_LAST_COMMAND_FILE"; or return
+    # Running `llm-cli fix` must not replace the command it is about to fix
+    string match -qr '^\s*(\S*/)?llm-cli(\s|$)' -- $argv[1]; and return
+    printf '%s\n%s\n' $exit_code "$argv[1]" >$LLM_CLI_LAST_COMMAND_FILE
+end
+
+function __llm_cli_exit --on-event fish_exit
+    test -n "$LLM_CLI_LAST_COMMAND_FILE"; and rm -f -- $LLM_CLI_LAST_COMMAND_FILE
+end
+
+# Replaces the command line with the command suggested for it by
+# `llm-cli suggest`, bound to Ctrl-X Ctrl-L
+function __llm_cli_suggest
+    set -l description (commandline | string collect)
+    test -n "$description"; or return
+    set -l suggestion (llm-cli suggest -- "$description" </dev/null 2>/dev/null | string collect)
+    if test -n "$suggestion"
+        commandline --replace -- $suggestion
+    else
+        echo "llm-cli suggest failed, run it directly to see why" >&2
+    end
+    commandline -f repaint
+end
+
+bind \cx\cl __llm_cli_suggest
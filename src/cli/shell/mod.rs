@@ -0,0 +1,104 @@
+use std::path::PathBuf;
+
+use clap::ValueEnum;
+
+/// Environment variable naming the file the shell integration records the
+/// last command in: its exit code on the first line, the command after it
+pub const LAST_COMMAND_ENV: &str = "LLM_CLI_LAST_COMMAND_FILE";
+/// Environment variable naming the file the bash and zsh integrations copy
+/// the error output of the last command to, if enabled with
+/// `LLM_CLI_CAPTURE_STDERR=1`
+pub const STDERR_ENV: &str = "LLM_CLI_STDERR_FILE";
+
+/// Shells with an integration script
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl Shell {
+    /// Returns the integration script, to be evaluated by the shell on startup
+    pub const fn init_script(self) -> &'static str {
+        match self {
+            Self::Bash => include_str!("init.bash"),
+            Self::Zsh => include_str!("init.zsh"),
+            Self::Fish => include_str!("init.fish"),
+        }
+    }
+}
+
+/// The last command recorded by the shell integration
+#[derive(Debug, PartialEq, Eq)]
+pub struct LastCommand {
+    pub command: String,
+    pub exit_code: Option<i32>,
+    /// File holding the error output of the command, if it was captured
+    pub stderr_file: Option<PathBuf>,
+}
+
+impl LastCommand {
+    /// Parses the file written by the integration scripts. Returns `None` if
+    /// it holds no command.
+    pub fn parse(contents: &str) -> Option<Self> {
+        let (exit_code, command) = contents.split_once('\n')?;
+        let command = command.trim();
+        if command.is_empty() {
+            return None;
+        }
+        Some(Self {
+            command: command.to_string(),
+            exit_code: exit_code.trim().parse().ok(),
+            stderr_file: None,
+        })
+    }
+
+    /// Reads the command recorded by the shell integration, if it is loaded,
+    /// together with the file holding its error output
+    pub fn recorded() -> Option<Self> {
+        let path = std::env::var_os(LAST_COMMAND_ENV)?;
+        let mut last = Self::parse(&std::fs::read_to_string(path).ok()?)?;
+        last.stderr_file = std::env::var_os(STDERR_ENV)
+            .map(PathBuf::from)
+            .filter(|path| path.is_file());
+        Some(last)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_last_command() {
+        assert_eq!(
+            LastCommand::parse("127\ngit psuh origin main\n"),
+            Some(LastCommand {
+                command: "git psuh origin main".to_string(),
+                exit_code: Some(127),
+                stderr_file: None,
+            })
+        );
+        assert_eq!(
+            LastCommand::parse("1\nfor f in *; do\n  wc -l $f\ndone\n").map(|last| last.command),
+            Some("for f in *; do\n  wc -l $f\ndone".to_string())
+        );
+        assert_eq!(
+            LastCommand::parse("\nmake\n").and_then(|last| last.exit_code),
+            None
+        );
+        assert_eq!(LastCommand::parse("0\n\n"), None);
+        assert_eq!(LastCommand::parse(""), None);
+    }
+
+    #[test]
+    fn test_scripts_record_commands_and_bind_widget() {
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish] {
+            let script = shell.init_script();
+            assert!(script.contains(LAST_COMMAND_ENV));
+            assert!(script.contains("llm-cli suggest -- "));
+            assert!(script.contains(STDERR_ENV));
+        }
+    }
+}
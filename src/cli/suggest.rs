@@ -0,0 +1,103 @@
+use std::path::Path;
+
+use serde_json::{json, Value};
+
+use super::run::create_llm_client;
+use crate::{
+    core::{Config, LLMError},
+    providers::Message,
+    tools::{execute_command::configured_shell, ToolDefinition},
+};
+
+/// Name of the tool the model answers with
+const SUGGESTION_TOOL: &str = "suggest_command";
+
+/// Definition of the tool whose arguments hold the suggested command
+fn suggestion_schema() -> ToolDefinition {
+    ToolDefinition {
+        name: SUGGESTION_TOOL.to_string(),
+        description: "Suggests a single shell command doing what the user asked for".to_string(),
+        parameters: json!({
+            "type": "object",
+            "properties": {
+                "command": {
+                    "type": "string",
+                    "description": "The command, ready to run, without explanation or code fences"
+                }
+            },
+            "required": ["command"]
+        }),
+    }
+}
+
+/// Asks the model for a command doing what the description says and prints
+/// only the command, so that shell widgets can put it on the command line.
+pub(super) async fn run(config: Config, debug: bool, description: &str) -> Result<(), LLMError> {
+    let shell = configured_shell(&config.tools);
+    let client = create_llm_client(config, debug)?;
+
+    let arguments = client
+        .query_structured(
+            &[Message::user(suggest_prompt(description, &shell))],
+            &suggestion_schema(),
+        )
+        .await?;
+    println!("{}", suggested_command(&arguments)?);
+    Ok(())
+}
+
+/// Builds the request for a command, naming the shell and system it runs on
+fn suggest_prompt(description: &str, shell: &str) -> String {
+    let shell = Path::new(shell)
+        .file_name()
+        .map_or_else(|| shell.into(), |name| name.to_string_lossy());
+    let prompt = format!(
+        "Suggest a single {shell} command for {} that does the following: {description}",
+        std::env::consts::OS
+    );
+    match std::env::current_dir() {
+        Ok(dir) => format!("{prompt}\n\nWorking directory: {}", dir.display()),
+        Err(_) => prompt,
+    }
+}
+
+/// Extracts the command from the arguments of the suggestion tool
+fn suggested_command(arguments: &Value) -> Result<String, LLMError> {
+    arguments["command"]
+        .as_str()
+        .map(str::trim)
+        .filter(|command| !command.is_empty())
+        .map(ToString::to_string)
+        .ok_or_else(|| LLMError::ResponseFormat("The model did not suggest a command".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_suggest_prompt_names_shell() {
+        let prompt = suggest_prompt("find big files", "/usr/bin/zsh");
+
+        assert!(prompt.starts_with(&format!(
+            "Suggest a single zsh command for {} that does the following: find big files",
+            std::env::consts::OS
+        )));
+    }
+
+    #[test]
+    fn test_suggested_command() {
+        assert_eq!(
+            suggested_command(&json!({ "command": " du -ah . | sort -rh | head\n" })).unwrap(),
+            "du -ah . | sort -rh | head"
+        );
+        assert!(matches!(
+            suggested_command(&json!({ "command": "" })),
+            Err(LLMError::ResponseFormat(_))
+        ));
+        assert!(matches!(
+            suggested_command(&json!({})),
+            Err(LLMError::ResponseFormat(_))
+        ));
+    }
+}
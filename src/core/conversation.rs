@@ -1,13 +1,13 @@
-use std::{io::Write, pin::Pin};
+use std::{collections::BTreeMap, io::Write, pin::Pin};
 
 use crate::providers::types::messages::Message;
-use crate::providers::{FinishReason, MessageChunk};
+use crate::providers::{FinishReason, MessageChunk, Usage};
 use crate::{
-    core::{error::ToolError, formatter::Formatter, LLMError},
+    core::{error::ToolError, formatter::Formatter, LLMError, OutputFormat},
     tools::ToolCall,
 };
 use crate::{providers::llm::LLMClient, tools::ToolRegistry};
-use futures::{Stream, StreamExt};
+use futures::{future::join_all, Stream, StreamExt};
 use log::debug;
 
 use super::formatter::SyntaxHighlighter;
@@ -18,6 +18,8 @@ pub struct ConversationManager {
     tool_registry: Option<ToolRegistry>,
     client: Box<dyn LLMClient>,
     formatter: Formatter<SyntaxHighlighter>,
+    output: OutputFormat,
+    usage: Usage,
 }
 
 impl ConversationManager {
@@ -36,9 +38,50 @@ impl ConversationManager {
             tool_registry,
             client,
             formatter,
+            output: OutputFormat::default(),
+            usage: Usage::default(),
         }
     }
 
+    /// Writes responses in the given format instead of formatted text.
+    /// Nothing is written for [`OutputFormat::Json`], whose object is only
+    /// complete once the run is.
+    #[must_use]
+    pub const fn with_output(mut self, output: OutputFormat) -> Self {
+        self.output = output;
+        self
+    }
+
+    /// Replaces the LLM client used for subsequent queries.
+    ///
+    /// # Arguments
+    /// * `client` - The new LLM client implementation
+    pub fn set_client(&mut self, client: Box<dyn LLMClient>) {
+        self.client = client;
+    }
+
+    /// Replaces the tool registry used for subsequent queries.
+    ///
+    /// # Arguments
+    /// * `tool_registry` - Optional registry containing available tools
+    pub fn set_tool_registry(&mut self, tool_registry: Option<ToolRegistry>) {
+        self.tool_registry = tool_registry;
+    }
+
+    /// Ends the output of a run that was cancelled while writing, so that no
+    /// highlighting carries over to the following output
+    pub fn finish_output<W: Write>(&mut self, writer: &mut W) -> Result<(), LLMError> {
+        if self.output == OutputFormat::Text {
+            self.formatter.finish(writer)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the token usage of the last run, summed over all of its steps
+    pub const fn usage(&self) -> Usage {
+        self.usage
+    }
+
     /// Runs the conversation loop, processing messages and executing tools as needed.
     ///
     /// # Arguments
@@ -55,6 +98,7 @@ impl ConversationManager {
         writer: &mut W,
     ) -> Result<Vec<Message>, LLMError> {
         let mut conversation_state = ConversationState::new(initial_messages);
+        self.usage = Usage::default();
         let tool_definitions = self
             .tool_registry
             .as_ref()
@@ -76,7 +120,7 @@ impl ConversationManager {
             }
 
             let tool_results = self.handle_tool_calls(&tool_calls).await?;
-            debug!("[Conversation] Tool results: {:?}", tool_results);
+            debug!("[Conversation] Tool results: {tool_results:?}");
             conversation_state.add_assistant_message(content, tool_calls);
             conversation_state.add_tool_results(tool_results);
         }
@@ -91,36 +135,43 @@ impl ConversationManager {
         writer: &mut W,
     ) -> Result<(String, Vec<ToolCall>), LLMError> {
         let mut content = String::new();
-        let mut tool_call_buffer = String::new();
         let mut tool_calls = Vec::new();
-        let mut current_tool_call: Option<ToolCall> = None;
+        // Tool calls being streamed with their argument fragments, by block index
+        let mut pending_tool_calls: BTreeMap<usize, (ToolCall, String)> = BTreeMap::new();
 
         while let Some(chunk) = stream.next().await {
             let chunk = chunk?;
+            if self.output == OutputFormat::Jsonl {
+                write_event(writer, &chunk)?;
+            }
             match chunk {
                 MessageChunk::Text(text) => {
                     self.write_chunk(writer, &text)?;
                     content.push_str(&text);
                 }
-                MessageChunk::ToolCallStart { id, name } => {
-                    current_tool_call = Some(ToolCall {
+                MessageChunk::ToolCallStart { index, id, name } => {
+                    let tool_call = ToolCall {
                         id,
                         name,
                         arguments: serde_json::Value::Null,
-                    });
+                    };
+                    pending_tool_calls.insert(index, (tool_call, String::new()));
                 }
-                MessageChunk::ToolCallArgument(tool_call_argument) => {
-                    tool_call_buffer.push_str(&tool_call_argument);
+                MessageChunk::ToolCallArgument { index, arguments } => {
+                    if let Some((_, buffer)) = pending_tool_calls.get_mut(&index) {
+                        buffer.push_str(&arguments);
+                    }
                 }
-                MessageChunk::ContentBlockStop => {
-                    if let Some(mut tool_call) = current_tool_call.take() {
-                        tool_call.arguments = serde_json::from_str(&tool_call_buffer)
-                            .unwrap_or(serde_json::Value::Null);
-                        tool_calls.push(tool_call);
-                        tool_call_buffer.clear();
+                MessageChunk::ContentBlockStop { index } => {
+                    if let Some(pending) = pending_tool_calls.remove(&index) {
+                        tool_calls.push(finish_tool_call(pending));
                     }
                 }
-                MessageChunk::TextStart => continue,
+                MessageChunk::Usage(usage) => {
+                    debug!("[Conversation] usage: {usage:?}");
+                    self.usage += usage;
+                }
+                MessageChunk::TextStart => {}
                 MessageChunk::End(finish_reason) => match finish_reason {
                     FinishReason::Stop => break,
                     FinishReason::Error(error) => {
@@ -130,19 +181,37 @@ impl ConversationManager {
             }
         }
 
-        self.formatter.finish(writer)?;
+        // Calls the stream did not explicitly end are complete once it is done
+        tool_calls.extend(pending_tool_calls.into_values().map(finish_tool_call));
+        if self.output == OutputFormat::Text {
+            self.formatter.finish(writer)?;
+        }
 
         Ok((content, tool_calls))
     }
 
-    /// Writes a chunk of content to the output writer.
+    /// Writes a chunk of content to the output writer, formatted unless raw
+    /// text is requested. Other formats write events instead.
     fn write_chunk<W: Write>(&mut self, writer: &mut W, content: &str) -> Result<(), LLMError> {
-        self.formatter.format_chunk(writer, content)?;
+        match self.output {
+            OutputFormat::Text => self.formatter.format_chunk(writer, content)?,
+            OutputFormat::Raw => writer.write_all(content.as_bytes())?,
+            OutputFormat::Json | OutputFormat::Jsonl => return Ok(()),
+        }
         writer.flush()?;
         Ok(())
     }
 
-    /// Executes a sequence of tool calls and returns their results.
+    /// Executes a sequence of tool calls and returns their results in the
+    /// order of the calls.
+    ///
+    /// Consecutive calls of read-only tools run concurrently. Other calls run
+    /// one at a time, so that confirmation prompts do not overlap and side
+    /// effects happen in the requested order.
+    ///
+    /// Failing tool calls produce error results for the model to react to.
+    /// Only fatal errors, such as tool calls while tools are disabled, are
+    /// returned.
     async fn handle_tool_calls(&self, tool_calls: &[ToolCall]) -> Result<Vec<Message>, ToolError> {
         let tool_registry = self.tool_registry.as_ref().ok_or_else(|| {
             let disabled_tools = tool_calls
@@ -154,17 +223,57 @@ impl ConversationManager {
         })?;
 
         let mut messages = Vec::with_capacity(tool_calls.len());
+        let batches = tool_calls.chunk_by(|a, b| {
+            tool_registry.is_read_only(&a.name) && tool_registry.is_read_only(&b.name)
+        });
 
-        for tool_call in tool_calls {
-            let result = tool_registry
-                .execute_tool(&tool_call.name, &tool_call.arguments)
-                .await?;
-
-            messages.push(Message::tool(result, &tool_call.id));
+        for batch in batches {
+            let results = join_all(
+                batch
+                    .iter()
+                    .map(|tool_call| Self::execute_tool_call(tool_registry, tool_call)),
+            )
+            .await;
+            for result in results {
+                messages.push(result?);
+            }
         }
 
         Ok(messages)
     }
+
+    /// Executes a single tool call, turning non-fatal errors into error results
+    async fn execute_tool_call(
+        tool_registry: &ToolRegistry,
+        tool_call: &ToolCall,
+    ) -> Result<Message, ToolError> {
+        match tool_registry
+            .execute_tool(&tool_call.name, &tool_call.arguments)
+            .await
+        {
+            Ok(result) => Ok(Message::tool(result, &tool_call.id)),
+            Err(e) if e.is_fatal() => Err(e),
+            Err(e) => {
+                eprintln!("{tool_call} failed: {e}");
+                Ok(Message::tool_error(e, &tool_call.id))
+            }
+        }
+    }
+}
+
+/// Writes a chunk as a line of JSON
+fn write_event<W: Write>(writer: &mut W, chunk: &MessageChunk) -> Result<(), LLMError> {
+    let event = serde_json::to_string(chunk)
+        .map_err(|e| LLMError::ResponseFormat(format!("Failed to serialize {chunk:?}: {e}")))?;
+    writeln!(writer, "{event}")?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Completes a streamed tool call by parsing its collected arguments
+fn finish_tool_call((mut tool_call, arguments): (ToolCall, String)) -> ToolCall {
+    tool_call.arguments = serde_json::from_str(&arguments).unwrap_or(serde_json::Value::Null);
+    tool_call
 }
 
 /// Maintains the state of an ongoing conversation.
@@ -190,3 +299,374 @@ impl ConversationState {
         self.messages.extend(results);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::llm::BoxStream;
+    use crate::tools::{Tool, ToolDefinition};
+    use serde_json::{json, Value};
+    use std::collections::VecDeque;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    /// Client replaying scripted streaming responses, one per query
+    struct ScriptedClient {
+        responses: Mutex<VecDeque<Vec<MessageChunk>>>,
+    }
+
+    impl ScriptedClient {
+        fn new(responses: Vec<Vec<MessageChunk>>) -> Self {
+            Self {
+                responses: Mutex::new(responses.into()),
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl LLMClient for ScriptedClient {
+        async fn query(
+            &self,
+            _messages: &[Message],
+            _tools: Option<&[ToolDefinition]>,
+        ) -> Result<Vec<Message>, LLMError> {
+            Err(LLMError::ApiError(
+                "only streaming is used by the conversation manager".to_string(),
+            ))
+        }
+
+        async fn query_streaming(
+            &self,
+            _messages: &[Message],
+            _tools: Option<&[ToolDefinition]>,
+        ) -> Result<BoxStream, LLMError> {
+            let chunks = self
+                .responses
+                .lock()
+                .unwrap()
+                .pop_front()
+                .expect("Unexpected query");
+            Ok(futures::stream::iter(chunks.into_iter().map(Ok)).boxed())
+        }
+    }
+
+    /// Tool returning its arguments
+    struct EchoTool;
+
+    #[async_trait::async_trait]
+    impl Tool for EchoTool {
+        fn definition(&self) -> ToolDefinition {
+            ToolDefinition {
+                name: "echo".to_string(),
+                description: "Echoes its arguments".to_string(),
+                parameters: json!({ "type": "object" }),
+            }
+        }
+
+        async fn execute(&self, arguments: &Value) -> Result<Value, ToolError> {
+            Ok(arguments.clone())
+        }
+    }
+
+    /// Tool taking a while to run, recording how many of its calls overlap
+    struct SlowTool {
+        name: &'static str,
+        read_only: bool,
+        running: Arc<AtomicUsize>,
+        max_running: Arc<AtomicUsize>,
+    }
+
+    #[async_trait::async_trait]
+    impl Tool for SlowTool {
+        fn definition(&self) -> ToolDefinition {
+            ToolDefinition {
+                name: self.name.to_string(),
+                description: "Sleeps".to_string(),
+                parameters: json!({ "type": "object" }),
+            }
+        }
+
+        fn is_read_only(&self) -> bool {
+            self.read_only
+        }
+
+        async fn execute(&self, arguments: &Value) -> Result<Value, ToolError> {
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_running.fetch_max(running, Ordering::SeqCst);
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            Ok(arguments.clone())
+        }
+    }
+
+    /// Runs the tool calls against a read-only `lookup` and a side-effecting
+    /// `change` tool and returns the results and the maximum concurrency
+    async fn run_slow_tool_calls(calls: &[(&str, &str)]) -> (Vec<Message>, usize) {
+        let running = Arc::new(AtomicUsize::new(0));
+        let max_running = Arc::new(AtomicUsize::new(0));
+        let mut registry = ToolRegistry::new();
+        for (name, read_only) in [("lookup", true), ("change", false)] {
+            registry.register(SlowTool {
+                name,
+                read_only,
+                running: Arc::clone(&running),
+                max_running: Arc::clone(&max_running),
+            });
+        }
+        let manager = ConversationManager::new(
+            Box::new(ScriptedClient::new(Vec::new())),
+            Some(registry),
+            Formatter::new(None),
+        );
+        let tool_calls: Vec<_> = calls
+            .iter()
+            .map(|(id, name)| ToolCall {
+                id: (*id).to_string(),
+                name: (*name).to_string(),
+                arguments: json!({ "id": id }),
+            })
+            .collect();
+
+        let messages = manager.handle_tool_calls(&tool_calls).await.unwrap();
+        (messages, max_running.load(Ordering::SeqCst))
+    }
+
+    fn usage(input_tokens: u64, output_tokens: u64) -> MessageChunk {
+        MessageChunk::Usage(Usage {
+            input_tokens,
+            output_tokens,
+            ..Usage::default()
+        })
+    }
+
+    fn manager(responses: Vec<Vec<MessageChunk>>) -> ConversationManager {
+        let mut registry = ToolRegistry::new();
+        registry.register(EchoTool);
+        ConversationManager::new(
+            Box::new(ScriptedClient::new(responses)),
+            Some(registry),
+            Formatter::new(None),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_usage_is_summed_over_tool_steps() {
+        let mut manager = manager(vec![
+            vec![
+                MessageChunk::tool_call_start(0, "call_1".to_string(), "echo".to_string()),
+                MessageChunk::tool_call_argument(0, r#"{"text":"hi"}"#.to_string()),
+                MessageChunk::ContentBlockStop { index: 0 },
+                usage(100, 20),
+                MessageChunk::stop(),
+            ],
+            vec![
+                MessageChunk::Text("done".to_string()),
+                usage(150, 5),
+                MessageChunk::stop(),
+            ],
+        ]);
+        let mut output = Vec::new();
+
+        let messages = manager
+            .run(vec![Message::user("echo hi")], 5, &mut output)
+            .await
+            .unwrap();
+
+        assert_eq!(messages.len(), 4);
+        assert_eq!(
+            manager.usage(),
+            Usage {
+                input_tokens: 250,
+                output_tokens: 25,
+                ..Usage::default()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_usage_is_reset_for_every_run() {
+        let mut manager = manager(vec![
+            vec![usage(10, 1), MessageChunk::stop()],
+            vec![usage(20, 2), MessageChunk::stop()],
+        ]);
+        let mut output = Vec::new();
+
+        for _ in 0..2 {
+            manager
+                .run(vec![Message::user("hi")], 5, &mut output)
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(manager.usage().input_tokens, 20);
+    }
+
+    #[tokio::test]
+    async fn test_raw_output_is_unformatted() {
+        let mut manager = manager(vec![vec![
+            MessageChunk::Text("Run:\n```sh\nls -la\n```".to_string()),
+            MessageChunk::stop(),
+        ]])
+        .with_output(OutputFormat::Raw);
+        let mut output = Vec::new();
+
+        manager
+            .run(vec![Message::user("list files")], 5, &mut output)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "Run:\n```sh\nls -la\n```"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_jsonl_output_has_an_event_per_chunk() {
+        let mut manager = manager(vec![vec![
+            MessageChunk::Text("Hi".to_string()),
+            usage(10, 2),
+            MessageChunk::stop(),
+        ]])
+        .with_output(OutputFormat::Jsonl);
+        let mut output = Vec::new();
+
+        manager
+            .run(vec![Message::user("hi")], 5, &mut output)
+            .await
+            .unwrap();
+
+        let events: Vec<Value> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0], json!({ "type": "text", "data": "Hi" }));
+        assert_eq!(events[1]["type"], "usage");
+        assert_eq!(events[1]["data"]["input_tokens"], 10);
+        assert_eq!(events[2], json!({ "type": "end", "data": "stop" }));
+    }
+
+    #[tokio::test]
+    async fn test_tool_errors_are_returned_to_the_model() {
+        let mut manager = manager(vec![
+            vec![
+                MessageChunk::tool_call_start(0, "call_1".to_string(), "missing".to_string()),
+                MessageChunk::tool_call_argument(0, "{}".to_string()),
+                MessageChunk::ContentBlockStop { index: 0 },
+                MessageChunk::stop(),
+            ],
+            vec![
+                MessageChunk::Text("sorry".to_string()),
+                MessageChunk::stop(),
+            ],
+        ]);
+        let mut output = Vec::new();
+
+        let messages = manager
+            .run(vec![Message::user("run it")], 5, &mut output)
+            .await
+            .unwrap();
+
+        assert_eq!(messages.len(), 4);
+        assert!(matches!(
+            &messages[2],
+            Message::ToolResult { tool_call_id, is_error: true, .. } if tool_call_id == "call_1"
+        ));
+        assert_eq!(messages[3].content(), "sorry");
+    }
+
+    #[tokio::test]
+    async fn test_tool_calls_while_disabled_abort() {
+        let mut manager = ConversationManager::new(
+            Box::new(ScriptedClient::new(vec![vec![
+                MessageChunk::tool_call_start(0, "call_1".to_string(), "echo".to_string()),
+                MessageChunk::ContentBlockStop { index: 0 },
+                MessageChunk::stop(),
+            ]])),
+            None,
+            Formatter::new(None),
+        );
+        let mut output = Vec::new();
+
+        let result = manager
+            .run(vec![Message::user("echo")], 5, &mut output)
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(LLMError::ToolError(ToolError::ToolCallsDisabled(_)))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_parallel_tool_calls_are_assembled_by_index() {
+        let mut manager = manager(vec![
+            vec![
+                MessageChunk::tool_call_start(0, "call_1".to_string(), "echo".to_string()),
+                MessageChunk::tool_call_argument(0, r#"{"text":"#.to_string()),
+                MessageChunk::tool_call_start(1, "call_2".to_string(), "echo".to_string()),
+                MessageChunk::tool_call_argument(1, r#"{"text":"b"}"#.to_string()),
+                MessageChunk::tool_call_argument(0, r#""a"}"#.to_string()),
+                MessageChunk::ContentBlockStop { index: 0 },
+                MessageChunk::ContentBlockStop { index: 1 },
+            ],
+            vec![MessageChunk::Text("done".to_string()), MessageChunk::stop()],
+        ]);
+        let mut output = Vec::new();
+
+        let messages = manager
+            .run(vec![Message::user("echo a and b")], 5, &mut output)
+            .await
+            .unwrap();
+
+        let Message::Assistant {
+            tool_calls: Some(tool_calls),
+            ..
+        } = &messages[1]
+        else {
+            panic!("Expected tool calls, got {:?}", messages[1]);
+        };
+        assert_eq!(tool_calls.len(), 2);
+        assert_eq!(tool_calls[0].arguments, json!({ "text": "a" }));
+        assert_eq!(tool_calls[1].arguments, json!({ "text": "b" }));
+        assert_eq!(messages[2].content(), r#"{"text":"a"}"#);
+        assert_eq!(messages[3].content(), r#"{"text":"b"}"#);
+    }
+
+    #[tokio::test]
+    async fn test_read_only_tool_calls_run_concurrently_in_order() {
+        let (messages, max_running) = run_slow_tool_calls(&[
+            ("call_1", "lookup"),
+            ("call_2", "lookup"),
+            ("call_3", "lookup"),
+        ])
+        .await;
+
+        assert_eq!(max_running, 3);
+        let ids: Vec<_> = messages
+            .iter()
+            .map(|message| match message {
+                Message::ToolResult { tool_call_id, .. } => tool_call_id.as_str(),
+                _ => panic!("Expected tool results, got {message:?}"),
+            })
+            .collect();
+        assert_eq!(ids, ["call_1", "call_2", "call_3"]);
+    }
+
+    #[tokio::test]
+    async fn test_other_tool_calls_run_one_at_a_time() {
+        let (messages, max_running) = run_slow_tool_calls(&[
+            ("call_1", "change"),
+            ("call_2", "lookup"),
+            ("call_3", "change"),
+            ("call_4", "change"),
+        ])
+        .await;
+
+        assert_eq!(max_running, 1);
+        assert_eq!(messages.len(), 4);
+        assert_eq!(messages[3].content(), r#"{"id":"call_4"}"#);
+    }
+}
@@ -1,3 +1,64 @@
+use std::fmt;
+use std::time::Duration;
+
+/// Error reported by a provider API, parsed from the error response
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProviderError {
+    /// HTTP status of the response, if the error was not sent mid-stream
+    pub status: Option<u16>,
+    /// Error type reported by the provider, e.g. `rate_limit_error`
+    pub error_type: Option<String>,
+    /// Error code reported by the provider, e.g. `context_length_exceeded`
+    pub code: Option<String>,
+    /// Human readable error message
+    pub message: String,
+    /// Request id to reference in support requests to the provider
+    pub request_id: Option<String>,
+    /// Delay the provider asked for before retrying the request
+    pub retry_after: Option<Duration>,
+}
+
+impl ProviderError {
+    /// Creates an error with the given HTTP status and message
+    pub fn new(status: Option<u16>, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Returns whether the provider reported the given value as error type or code
+    fn is(&self, kind: &str) -> bool {
+        self.error_type.as_deref() == Some(kind) || self.code.as_deref() == Some(kind)
+    }
+}
+
+impl fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)?;
+
+        let details: Vec<String> = [
+            self.status.map(|status| format!("status {status}")),
+            self.error_type
+                .as_ref()
+                .map(|error_type| format!("type {error_type}")),
+            self.code.as_ref().map(|code| format!("code {code}")),
+            self.request_id
+                .as_ref()
+                .map(|id| format!("request id {id}")),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+
+        if !details.is_empty() {
+            write!(f, " ({})", details.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum LLMError {
     /// Network-related errors
@@ -17,7 +78,22 @@ pub enum LLMError {
     ToolError(ToolError),
     /// Authentication-specific errors
     #[error("Authentication error: {0}")]
-    Authentication(String),
+    Authentication(ProviderError),
+    /// The provider rejected the request because of a rate limit
+    #[error("Rate limit exceeded: {0}")]
+    RateLimited(ProviderError),
+    /// The provider is temporarily overloaded
+    #[error("API overloaded: {0}")]
+    Overloaded(ProviderError),
+    /// The conversation does not fit into the context window of the model
+    #[error("Context length exceeded: {0}")]
+    ContextLengthExceeded(ProviderError),
+    /// The provider rejected the request as malformed or unsupported
+    #[error("Invalid request: {0}")]
+    InvalidRequest(ProviderError),
+    /// Any other error reported by the provider
+    #[error("API error: {0}")]
+    Provider(ProviderError),
     /// Stream-related errors
     #[error("Stream error: {0}")]
     StreamError(String),
@@ -29,7 +105,7 @@ pub enum LLMError {
     NotFound(String),
     /// Server error
     #[error("Server error: {0}")]
-    ServerError(String),
+    ServerError(ProviderError),
     /// I/O error
     #[error("I/O error: {0}")]
     IOError(String),
@@ -55,6 +131,90 @@ pub enum ToolError {
     /// Invalid argument error
     #[error("Invalid argument: {0}")]
     InvalidArgument(String),
+    /// The permission policy or the user refused the tool call
+    #[error("Permission denied: {0}")]
+    PermissionDenied(String),
+}
+
+impl ToolError {
+    /// Returns whether the error ends the conversation. Other errors are
+    /// returned to the model as the result of the tool call.
+    pub const fn is_fatal(&self) -> bool {
+        matches!(self, Self::ToolCallsDisabled(_))
+    }
+}
+
+impl LLMError {
+    /// Returns the error reported by the provider, if any
+    pub const fn provider_error(&self) -> Option<&ProviderError> {
+        match self {
+            Self::Authentication(error)
+            | Self::RateLimited(error)
+            | Self::Overloaded(error)
+            | Self::ContextLengthExceeded(error)
+            | Self::InvalidRequest(error)
+            | Self::Provider(error)
+            | Self::ServerError(error) => Some(error),
+            _ => None,
+        }
+    }
+
+    /// Returns whether the failed request may succeed when sent again.
+    ///
+    /// Of the network errors only failed connections are retried. After a
+    /// timeout the provider may already be generating a response, so sending
+    /// the request again could bill it twice.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::RateLimited(_) | Self::Overloaded(_) | Self::ServerError(_) => true,
+            Self::Provider(error) => error.status == Some(408),
+            Self::Network(error) => error.is_connect(),
+            _ => false,
+        }
+    }
+
+    /// Returns the delay the provider asked for before retrying
+    pub fn retry_after(&self) -> Option<Duration> {
+        self.provider_error().and_then(|error| error.retry_after)
+    }
+}
+
+impl From<ProviderError> for LLMError {
+    /// Classifies a provider error by its error type, code and HTTP status
+    fn from(error: ProviderError) -> Self {
+        let message = error.message.to_lowercase();
+        if error.is("context_length_exceeded")
+            || message.contains("prompt is too long")
+            || message.contains("maximum context length")
+        {
+            return Self::ContextLengthExceeded(error);
+        }
+        if error.is("insufficient_quota") {
+            // Reported with status 429, but retrying does not help
+            return Self::Provider(error);
+        }
+
+        match error.status {
+            _ if error.is("authentication_error")
+                || error.is("permission_error")
+                || error.is("invalid_api_key") =>
+            {
+                Self::Authentication(error)
+            }
+            _ if error.is("rate_limit_error") || error.is("rate_limit_exceeded") => {
+                Self::RateLimited(error)
+            }
+            _ if error.is("overloaded_error") => Self::Overloaded(error),
+            _ if error.is("invalid_request_error") => Self::InvalidRequest(error),
+            _ if error.is("api_error") || error.is("server_error") => Self::ServerError(error),
+            Some(401 | 403) => Self::Authentication(error),
+            Some(429) => Self::RateLimited(error),
+            Some(529) => Self::Overloaded(error),
+            Some(400 | 413 | 422) => Self::InvalidRequest(error),
+            Some(500..=599) => Self::ServerError(error),
+            _ => Self::Provider(error),
+        }
+    }
 }
 
 impl From<ToolError> for LLMError {
@@ -74,10 +234,10 @@ impl From<reqwest::Error> for LLMError {
         // If the error has a status code, map it to a more specific error
         if let Some(status) = err.status() {
             match status.as_u16() {
-                401 | 403 => Self::Authentication(format!("Authentication failed: {err}")),
                 404 => Self::NotFound(format!("Resource not found: {err}")),
-                429 => Self::ApiError(format!("Rate limit exceeded: {err}")),
-                500..=599 => Self::ServerError(format!("Server error: {err}")),
+                status @ (401 | 403 | 429 | 500..=599) => {
+                    ProviderError::new(Some(status), err.to_string()).into()
+                }
                 _ => Self::Network(err),
             }
         } else {
@@ -86,3 +246,103 @@ impl From<reqwest::Error> for LLMError {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn provider_error(status: u16, error_type: Option<&str>, code: Option<&str>) -> ProviderError {
+        ProviderError {
+            error_type: error_type.map(String::from),
+            code: code.map(String::from),
+            ..ProviderError::new(Some(status), "error message")
+        }
+    }
+
+    #[test]
+    fn test_classify_by_error_type() {
+        assert!(matches!(
+            LLMError::from(provider_error(400, Some("authentication_error"), None)),
+            LLMError::Authentication(_)
+        ));
+        assert!(matches!(
+            LLMError::from(provider_error(400, Some("rate_limit_error"), None)),
+            LLMError::RateLimited(_)
+        ));
+        assert!(matches!(
+            LLMError::from(provider_error(500, Some("overloaded_error"), None)),
+            LLMError::Overloaded(_)
+        ));
+        assert!(matches!(
+            LLMError::from(provider_error(404, Some("invalid_request_error"), None)),
+            LLMError::InvalidRequest(_)
+        ));
+        assert!(matches!(
+            LLMError::from(provider_error(
+                400,
+                Some("invalid_request_error"),
+                Some("context_length_exceeded")
+            )),
+            LLMError::ContextLengthExceeded(_)
+        ));
+        assert!(matches!(
+            LLMError::from(provider_error(429, Some("insufficient_quota"), None)),
+            LLMError::Provider(_)
+        ));
+    }
+
+    #[test]
+    fn test_classify_by_status() {
+        assert!(matches!(
+            LLMError::from(provider_error(403, None, None)),
+            LLMError::Authentication(_)
+        ));
+        assert!(matches!(
+            LLMError::from(provider_error(429, None, None)),
+            LLMError::RateLimited(_)
+        ));
+        assert!(matches!(
+            LLMError::from(provider_error(503, None, None)),
+            LLMError::ServerError(_)
+        ));
+        assert!(matches!(
+            LLMError::from(provider_error(404, None, None)),
+            LLMError::Provider(_)
+        ));
+    }
+
+    #[test]
+    fn test_context_length_from_message() {
+        let error = ProviderError::new(
+            Some(400),
+            "prompt is too long: 210000 tokens > 200000 maximum",
+        );
+        assert!(matches!(
+            LLMError::from(error),
+            LLMError::ContextLengthExceeded(_)
+        ));
+    }
+
+    #[test]
+    fn test_retryable_errors() {
+        assert!(LLMError::from(provider_error(429, None, None)).is_retryable());
+        assert!(LLMError::from(provider_error(529, None, None)).is_retryable());
+        assert!(LLMError::from(provider_error(502, None, None)).is_retryable());
+        assert!(!LLMError::from(provider_error(400, None, None)).is_retryable());
+        assert!(
+            !LLMError::from(provider_error(429, None, Some("insufficient_quota"))).is_retryable()
+        );
+    }
+
+    #[test]
+    fn test_display_includes_details() {
+        let error = ProviderError {
+            request_id: Some("req_123".to_string()),
+            ..provider_error(429, Some("rate_limit_error"), None)
+        };
+        assert_eq!(
+            error.to_string(),
+            "error message (status 429, type rate_limit_error, request id req_123)"
+        );
+    }
+}
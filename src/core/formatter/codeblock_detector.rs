@@ -33,7 +33,7 @@ impl CodeBlockDetector {
     }
 
     #[inline]
-    pub fn handle_backtick(&mut self) {
+    pub const fn handle_backtick(&mut self) {
         self.pending_backticks += 1;
     }
 
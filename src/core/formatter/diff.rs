@@ -0,0 +1,113 @@
+use std::io;
+
+use similar::{ChangeTag, TextDiff};
+use termcolor::{Color, ColorSpec, WriteColor};
+
+/// Lines of unchanged context shown around every change
+const CONTEXT_LINES: usize = 3;
+
+/// Number of lines added and removed by a change
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DiffStats {
+    pub added: usize,
+    pub removed: usize,
+}
+
+/// Counts the lines added and removed between two versions of a text
+pub fn diff_stats(old: &str, new: &str) -> DiffStats {
+    TextDiff::from_lines(old, new).iter_all_changes().fold(
+        DiffStats::default(),
+        |mut stats, change| {
+            match change.tag() {
+                ChangeTag::Insert => stats.added += 1,
+                ChangeTag::Delete => stats.removed += 1,
+                ChangeTag::Equal => {}
+            }
+            stats
+        },
+    )
+}
+
+/// Writes a colorized unified diff between two versions of a file.
+///
+/// # Arguments
+/// * `writer` - The output, colors are only written if it supports them
+/// * `path` - The path shown in the header
+/// * `old` - The current content, `None` for a file that does not exist yet
+/// * `new` - The proposed content
+pub fn write_diff<W: WriteColor>(
+    writer: &mut W,
+    path: &str,
+    old: Option<&str>,
+    new: &str,
+) -> io::Result<()> {
+    let old_name = old.map_or_else(|| "/dev/null".to_string(), |_| format!("a/{path}"));
+    let diff = TextDiff::from_lines(old.unwrap_or_default(), new);
+
+    writer.set_color(ColorSpec::new().set_bold(true))?;
+    writeln!(writer, "--- {old_name}")?;
+    writeln!(writer, "+++ b/{path}")?;
+
+    for hunk in diff
+        .unified_diff()
+        .context_radius(CONTEXT_LINES)
+        .iter_hunks()
+    {
+        writer.set_color(ColorSpec::new().set_fg(Some(Color::Cyan)))?;
+        writeln!(writer, "{}", hunk.header())?;
+
+        for change in hunk.iter_changes() {
+            let (sign, color) = match change.tag() {
+                ChangeTag::Delete => ('-', Some(Color::Red)),
+                ChangeTag::Insert => ('+', Some(Color::Green)),
+                ChangeTag::Equal => (' ', None),
+            };
+            writer.set_color(ColorSpec::new().set_fg(color))?;
+            write!(writer, "{sign}{}", change.value())?;
+            if change.missing_newline() {
+                writeln!(writer)?;
+                writer.reset()?;
+                writeln!(writer, "\\ No newline at end of file")?;
+            }
+        }
+    }
+    writer.reset()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use termcolor::NoColor;
+
+    fn render(old: Option<&str>, new: &str) -> String {
+        let mut output = NoColor::new(Vec::new());
+        write_diff(&mut output, "src/lib.rs", old, new).unwrap();
+        String::from_utf8(output.into_inner()).unwrap()
+    }
+
+    #[test]
+    fn test_unified_diff_of_changed_file() {
+        let old = "a\nb\nc\nd\ne\nf\ng\nh\n";
+        let new = "a\nb\nc\nd\nE\nf\ng\nh\n";
+
+        assert_eq!(
+            render(Some(old), new),
+            "--- a/src/lib.rs\n+++ b/src/lib.rs\n@@ -2,7 +2,7 @@\n b\n c\n d\n-e\n+E\n f\n g\n h\n"
+        );
+        assert_eq!(
+            diff_stats(old, new),
+            DiffStats {
+                added: 1,
+                removed: 1
+            }
+        );
+    }
+
+    #[test]
+    fn test_unified_diff_of_new_file() {
+        assert_eq!(
+            render(None, "fn main() {}"),
+            "--- /dev/null\n+++ b/src/lib.rs\n@@ -0,0 +1 @@\n+fn main() {}\n\\ No newline at end of file\n"
+        );
+    }
+}
@@ -1,4 +1,5 @@
 mod codeblock_detector;
+pub mod diff;
 mod syntax_highlighter;
 
 use codeblock_detector::StateTransition;
@@ -269,6 +270,7 @@ mod tests {
     // This means that the code block will be highlighted even if it's not closed.
     #[test]
     #[ignore]
+    #[allow(clippy::ignore_without_reason)] // The reason is explained above
     fn test_single_backtick() {
         assert_eq!(format_text("a`b"), "a`b");
     }
@@ -2,9 +2,20 @@ mod config;
 pub mod conversation;
 pub mod error;
 pub mod formatter;
+mod output;
+mod paths;
+pub mod session;
 
 pub use config::Config;
+pub use config::CustomToolConfig;
+pub use config::McpServerConfig;
+pub use config::ModelPrice;
+pub use config::PermissionsConfig;
+pub use config::Profile;
 pub use config::Provider;
-pub use config::ProviderConfig;
-pub use error::LLMError;
+pub use config::RetryConfig;
+pub use config::ToolsConfig;
+pub use error::{LLMError, ProviderError};
 pub use formatter::Formatter;
+pub use output::{response_json, OutputFormat};
+pub use session::{Session, SessionStore};
@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+
+use clap::ValueEnum;
+use serde_json::{json, Value};
+
+use crate::providers::{Message, Usage};
+
+/// How responses are written to stdout
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    /// Text with highlighted code blocks
+    #[default]
+    Text,
+    /// Text exactly as the model wrote it
+    Raw,
+    /// A JSON object with the final message once the response is complete
+    Json,
+    /// A JSON event per line for every chunk of the streamed response
+    Jsonl,
+}
+
+impl OutputFormat {
+    /// Whether the output is meant for other programs, rather than for reading
+    pub const fn is_machine_readable(self) -> bool {
+        matches!(self, Self::Json | Self::Jsonl)
+    }
+}
+
+/// Describes a response for `--output json`: the final message of the model,
+/// the model and usage, and the tool calls made on the way with their results
+///
+/// # Arguments
+/// * `messages` - The messages added by the run, after the user message
+/// * `model` - The model that responded
+/// * `usage` - The token usage of the run
+pub fn response_json(messages: &[Message], model: &str, usage: Usage) -> Value {
+    let content = messages
+        .iter()
+        .rev()
+        .find_map(|message| match message {
+            Message::Assistant { content, .. } => Some(content.as_str()),
+            _ => None,
+        })
+        .unwrap_or_default();
+    let results: HashMap<&str, (&Value, bool)> = messages
+        .iter()
+        .filter_map(|message| match message {
+            Message::ToolResult {
+                content,
+                tool_call_id,
+                is_error,
+            } => Some((tool_call_id.as_str(), (content, *is_error))),
+            _ => None,
+        })
+        .collect();
+    let tool_calls = messages
+        .iter()
+        .filter_map(|message| match message {
+            Message::Assistant {
+                tool_calls: Some(calls),
+                ..
+            } => Some(calls),
+            _ => None,
+        })
+        .flatten()
+        .map(|call| {
+            let (result, is_error) = results
+                .get(call.id.as_str())
+                .copied()
+                .unwrap_or((&Value::Null, false));
+            json!({
+                "id": call.id,
+                "name": call.name,
+                "arguments": call.arguments,
+                "result": result,
+                "is_error": is_error,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    json!({
+        "content": content,
+        "model": model,
+        "usage": usage,
+        "tool_calls": tool_calls,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::ToolCall;
+
+    #[test]
+    fn test_response_json() {
+        let messages = vec![
+            Message::assistant(
+                "Let me check.",
+                Some(vec![ToolCall {
+                    id: "call_1".to_string(),
+                    name: "execute_command".to_string(),
+                    arguments: json!({ "command": "df -h" }),
+                }]),
+            ),
+            Message::tool_error("'df -h' was denied by --never", "call_1"),
+            Message::assistant("I could not check the disk usage.", None),
+        ];
+        let usage = Usage {
+            input_tokens: 120,
+            output_tokens: 30,
+            ..Usage::default()
+        };
+
+        let response = response_json(&messages, "gpt-4o", usage);
+
+        assert_eq!(response["content"], "I could not check the disk usage.");
+        assert_eq!(response["model"], "gpt-4o");
+        assert_eq!(response["usage"]["input_tokens"], 120);
+        assert_eq!(
+            response["tool_calls"],
+            json!([{
+                "id": "call_1",
+                "name": "execute_command",
+                "arguments": { "command": "df -h" },
+                "result": "'df -h' was denied by --never",
+                "is_error": true,
+            }])
+        );
+    }
+}
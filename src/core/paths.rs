@@ -0,0 +1,33 @@
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// Name of the application directory inside the XDG base directories
+const APP_DIR: &str = "llm-cli";
+
+/// Resolves an XDG base directory from its environment variable, falling back
+/// to the given path relative to `$HOME` when the variable is unset or empty.
+fn xdg_dir(var: &str, home_fallback: &str) -> Option<PathBuf> {
+    env::var_os(var)
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(home_fallback)))
+}
+
+/// Returns the application data directory (`$XDG_DATA_HOME/llm-cli`)
+pub fn data_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_DATA_HOME", ".local/share").map(|dir| dir.join(APP_DIR))
+}
+
+/// Returns the application configuration directory (`$XDG_CONFIG_HOME/llm-cli`)
+pub fn config_dir() -> Option<PathBuf> {
+    xdg_dir("XDG_CONFIG_HOME", ".config").map(|dir| dir.join(APP_DIR))
+}
+
+/// Searches for a file in the given directory and all of its ancestors,
+/// returning the path of the closest match
+pub fn find_upwards(start: &Path, file_name: &str) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .map(|dir| dir.join(file_name))
+        .find(|path| path.is_file())
+}
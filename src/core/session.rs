@@ -0,0 +1,316 @@
+use std::collections::hash_map::RandomState;
+use std::fs;
+use std::hash::BuildHasher;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use super::{paths, Config, LLMError, Provider};
+use crate::providers::{Message, Usage};
+
+/// Name of the file pointing at the most recently saved session
+const LAST_SESSION_FILE: &str = ".last";
+/// File extension used for stored sessions
+const SESSION_EXTENSION: &str = "json";
+
+/// Returns the current time as seconds since the Unix epoch
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+/// Generates a session name from the creation time and a random suffix, so
+/// that sessions started in the same second get different names
+fn generate_name(created_at: u64) -> String {
+    let suffix = RandomState::new().hash_one(created_at) & 0xffff_ffff;
+    format!("session-{created_at}-{suffix:08x}")
+}
+
+/// Writes a file only the current user can read, since sessions hold whole
+/// conversations including file contents and command output
+#[cfg(unix)]
+fn write_private(path: &Path, contents: &str) -> io::Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?;
+    // Files saved by earlier versions may still be readable by others
+    file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    file.write_all(contents.as_bytes())
+}
+
+#[cfg(not(unix))]
+fn write_private(path: &Path, contents: &str) -> io::Result<()> {
+    fs::write(path, contents)
+}
+
+/// A conversation persisted to disk together with the provider and model used
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    /// Unique name of the session, used as its file name
+    pub name: String,
+    /// Profile used for the most recent turn
+    #[serde(default)]
+    pub profile: Option<String>,
+    /// Provider used for the most recent turn
+    pub provider: Provider,
+    /// Model used for the most recent turn
+    pub model: String,
+    /// Creation time in seconds since the Unix epoch
+    pub created_at: u64,
+    /// Last update time in seconds since the Unix epoch
+    pub updated_at: u64,
+    /// All user, assistant and tool result messages of the conversation
+    pub messages: Vec<Message>,
+    /// Token usage of all turns
+    #[serde(default)]
+    pub usage: Usage,
+    /// Estimated cost in USD of all turns with a known model price
+    #[serde(default)]
+    pub cost: f64,
+}
+
+impl Session {
+    /// Creates a new, empty session
+    ///
+    /// # Arguments
+    /// * `name` - Name of the session, generated from the current time and a
+    ///   random suffix if `None`
+    /// * `config` - The configuration whose active profile is used for the conversation
+    pub fn new(name: Option<String>, config: &Config) -> Self {
+        let created_at = now();
+        Self {
+            name: name.unwrap_or_else(|| generate_name(created_at)),
+            profile: Some(config.profile.clone()),
+            provider: config.get_provider(),
+            model: config.get_model().to_string(),
+            created_at,
+            updated_at: created_at,
+            messages: Vec::new(),
+            usage: Usage::default(),
+            cost: 0.0,
+        }
+    }
+}
+
+/// Stores sessions as JSON files in a directory
+#[derive(Debug, Clone)]
+pub struct SessionStore {
+    dir: PathBuf,
+}
+
+impl SessionStore {
+    /// Creates a store that keeps its sessions in the given directory
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Creates a store in the default location (`$XDG_DATA_HOME/llm-cli/sessions`)
+    pub fn open_default() -> Result<Self, LLMError> {
+        paths::data_dir()
+            .map(|dir| Self::new(dir.join("sessions")))
+            .ok_or_else(|| {
+                LLMError::ConfigError(
+                    "Could not determine data directory, set XDG_DATA_HOME or HOME".to_string(),
+                )
+            })
+    }
+
+    /// Loads the session with the given name, if it exists
+    pub fn load(&self, name: &str) -> Result<Option<Session>, LLMError> {
+        let path = self.session_path(name)?;
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let contents = fs::read_to_string(&path)?;
+        serde_json::from_str(&contents).map(Some).map_err(|e| {
+            LLMError::ConfigError(format!("Failed to parse session '{}': {e}", path.display()))
+        })
+    }
+
+    /// Loads the most recently saved session, if any
+    pub fn load_last(&self) -> Result<Option<Session>, LLMError> {
+        let pointer = self.dir.join(LAST_SESSION_FILE);
+        if !pointer.exists() {
+            return Ok(None);
+        }
+
+        let name = fs::read_to_string(pointer)?;
+        self.load(name.trim())
+    }
+
+    /// Saves the session and marks it as the most recent one
+    pub fn save(&self, session: &mut Session) -> Result<(), LLMError> {
+        let path = self.session_path(&session.name)?;
+        fs::create_dir_all(&self.dir)?;
+
+        session.updated_at = now();
+        let contents = serde_json::to_string_pretty(session)
+            .map_err(|e| LLMError::ConfigError(format!("Failed to serialize session: {e}")))?;
+        write_private(&path, &contents)?;
+        write_private(&self.dir.join(LAST_SESSION_FILE), &session.name)?;
+        Ok(())
+    }
+
+    /// Builds the file path for a session, rejecting names that could escape the store
+    fn session_path(&self, name: &str) -> Result<PathBuf, LLMError> {
+        let is_valid = !name.is_empty()
+            && !name.starts_with('.')
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+
+        if is_valid {
+            Ok(self.dir.join(format!("{name}.{SESSION_EXTENSION}")))
+        } else {
+            Err(LLMError::ConfigError(format!(
+                "Invalid session name '{name}': use letters, digits, '-', '_' and '.'"
+            )))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::ToolCall;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    fn sample_session(name: &str) -> Session {
+        let mut session = Session::new(Some(name.to_string()), &Config::default());
+        session.model = "claude-test".to_string();
+        session.messages = vec![
+            Message::user("list files"),
+            Message::assistant(
+                "",
+                Some(vec![ToolCall {
+                    id: "call_1".to_string(),
+                    name: "execute_command".to_string(),
+                    arguments: json!({ "command": "ls" }),
+                }]),
+            ),
+            Message::tool(json!("stdout: Cargo.toml"), "call_1"),
+            Message::assistant("There is a Cargo.toml", None),
+        ];
+        session
+    }
+
+    #[test]
+    fn test_save_and_load_round_trip() {
+        let dir = TempDir::new().unwrap();
+        let store = SessionStore::new(dir.path());
+        let mut session = sample_session("work");
+
+        store.save(&mut session).unwrap();
+        let loaded = store.load("work").unwrap().expect("session should exist");
+
+        assert_eq!(loaded.name, "work");
+        assert_eq!(loaded.model, "claude-test");
+        assert_eq!(loaded.messages.len(), 4);
+        assert!(matches!(
+            &loaded.messages[1],
+            Message::Assistant { tool_calls: Some(calls), .. } if calls[0].id == "call_1"
+        ));
+    }
+
+    #[test]
+    fn test_load_last() {
+        let dir = TempDir::new().unwrap();
+        let store = SessionStore::new(dir.path());
+        assert!(store.load_last().unwrap().is_none());
+
+        store.save(&mut sample_session("first")).unwrap();
+        store.save(&mut sample_session("second")).unwrap();
+
+        let last = store
+            .load_last()
+            .unwrap()
+            .expect("last session should exist");
+        assert_eq!(last.name, "second");
+    }
+
+    #[test]
+    fn test_generated_names_are_unique() {
+        let config = Config::default();
+        let first = Session::new(None, &config);
+        let second = Session::new(None, &config);
+
+        assert!(first
+            .name
+            .starts_with(&format!("session-{}-", first.created_at)));
+        assert_ne!(first.name, second.name);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_sessions_are_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TempDir::new().unwrap();
+        let store = SessionStore::new(dir.path());
+        let path = dir.path().join("work.json");
+        fs::write(&path, "{}").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+
+        store.save(&mut sample_session("work")).unwrap();
+
+        for file in [path, dir.path().join(LAST_SESSION_FILE)] {
+            let mode = fs::metadata(&file).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600, "{}", file.display());
+        }
+    }
+
+    #[test]
+    fn test_load_missing_session() {
+        let dir = TempDir::new().unwrap();
+        let store = SessionStore::new(dir.path());
+        assert!(store.load("missing").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_invalid_session_names() {
+        let dir = TempDir::new().unwrap();
+        let store = SessionStore::new(dir.path());
+
+        for name in ["", "../escape", ".hidden", "a/b", "with space"] {
+            assert!(
+                matches!(store.load(name), Err(LLMError::ConfigError(_))),
+                "Expected invalid name: {name}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_message_format_is_stable() {
+        let messages = sample_session("format").messages;
+        let value = serde_json::to_value(&messages).unwrap();
+
+        assert_eq!(
+            value,
+            json!([
+                { "role": "user", "content": "list files" },
+                {
+                    "role": "assistant",
+                    "content": "",
+                    "tool_calls": [{
+                        "id": "call_1",
+                        "name": "execute_command",
+                        "arguments": { "command": "ls" }
+                    }]
+                },
+                { "role": "tool_result", "content": "stdout: Cargo.toml", "tool_call_id": "call_1" },
+                { "role": "assistant", "content": "There is a Cargo.toml" }
+            ])
+        );
+    }
+}
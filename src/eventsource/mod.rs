@@ -194,6 +194,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::duration_suboptimal_units)] // Mirrors the `retry: 5000` field
     fn test_event_parse_complex() {
         let input = "id: 123\nevent: update\ndata: line1\ndata: line2\nretry: 5000\n\n";
         let event = Event::parse(input).unwrap();
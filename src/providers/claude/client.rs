@@ -1,20 +1,25 @@
-use crate::core::{Config, LLMError};
+use crate::core::{Config, LLMError, ProviderError};
 use crate::eventsource::{Event, EventSourceExt};
+use crate::providers::error::check_response;
 use crate::providers::llm::{BoxStream, LLMClient};
+use crate::providers::retry::send_with_retry;
 use crate::providers::Message as LLMMessage;
 use crate::providers::MessageChunk as LLMMessageChunk;
+use crate::providers::Usage as LLMUsage;
 use crate::tools::ToolDefinition as LLMToolDefinition;
 use async_stream::try_stream;
 use async_trait::async_trait;
 use futures::{Stream, StreamExt};
 use reqwest::{
     header::{HeaderMap, HeaderValue},
-    Client, StatusCode,
+    Client,
 };
+use serde_json::Value;
 
-use super::types::request::Tool;
+use super::types::request::{Tool, ToolChoice};
 use super::types::{
-    ChatCompletionRequest, ContentBlock, DeltaEvent, Message, MessageResponse, StreamEvent,
+    ChatCompletionRequest, ContentBlock, DeltaEvent, ErrorResponse, Message, MessageResponse,
+    StreamEvent,
 };
 
 const API_VERSION: &str = "2023-06-01";
@@ -60,6 +65,47 @@ impl ClaudeClient {
         headers
     }
 
+    /// Builds a chat completion request from the generic messages and tools,
+    /// including the system prompt and temperature of the active profile
+    fn build_request<'a>(
+        &'a self,
+        messages: &'a [LLMMessage],
+        tools: Option<&'a [LLMToolDefinition]>,
+    ) -> ChatCompletionRequest<'a> {
+        let model = self.config.get_model();
+        let max_tokens = self.config.get_max_tokens();
+
+        let claude_messages: Vec<Message> = messages.iter().map(Message::from).collect();
+        let mut request = ChatCompletionRequest::new(model, max_tokens, claude_messages);
+
+        if let Some(system_prompt) = self.config.get_system_prompt() {
+            request = request.with_system(system_prompt);
+        }
+
+        if let Some(temperature) = self.config.get_temperature() {
+            request = request.with_temperature(temperature);
+        }
+
+        if let Some(tools) = tools {
+            let claude_tools: Vec<Tool> = tools.iter().map(Tool::from).collect();
+            request = request.with_tools(claude_tools);
+        }
+
+        request
+    }
+
+    /// Builds a request requiring the model to call the given tool
+    fn build_structured_request<'a>(
+        &'a self,
+        messages: &'a [LLMMessage],
+        schema: &'a LLMToolDefinition,
+    ) -> ChatCompletionRequest<'a> {
+        self.build_request(messages, Some(std::slice::from_ref(schema)))
+            .with_tool_choice(ToolChoice::Tool {
+                name: schema.name.clone(),
+            })
+    }
+
     async fn request_chat_completion(
         &self,
         request: ChatCompletionRequest<'_>,
@@ -70,30 +116,16 @@ impl ClaudeClient {
             headers.insert("accept", HeaderValue::from_static("text/event-stream"));
         }
 
-        let response = self
-            .client
-            .post(format!("{API_BASE_URL}/messages"))
-            .headers(headers)
-            .json(&request)
-            .send()
-            .await
-            .map_err(|e| LLMError::ApiError(format!("Request failed: {e}")))?;
-
-        match response.status() {
-            StatusCode::OK => Ok(response),
-            StatusCode::UNAUTHORIZED => Err(LLMError::ApiError(
-                "Invalid API key or unauthorized access".to_string(),
-            )),
-            status => {
-                let error_text = response
-                    .text()
-                    .await
-                    .unwrap_or_else(|_| "Unknown error".to_string());
-                Err(LLMError::ApiError(format!(
-                    "API request failed with status {status}: {error_text}",
-                )))
-            }
-        }
+        send_with_retry(&self.config.retry, || {
+            let response = self
+                .client
+                .post(format!("{API_BASE_URL}/messages"))
+                .headers(headers.clone())
+                .json(&request)
+                .send();
+            async move { check_response::<ErrorResponse>(response.await?).await }
+        })
+        .await
     }
 
     /// Convert event stream to text stream
@@ -129,26 +161,28 @@ where
     S: Stream<Item = Result<StreamEvent<'a>, LLMError>> + Send + Unpin,
 {
     try_stream! {
+        let mut usage = LLMUsage::default();
         while let Some(event) = stream.next().await {
             let event = event?;
             match event {
-                StreamEvent::MessageStart { .. } => continue,
-                StreamEvent::ContentBlockStart { content_block, .. } => {
-                    match content_block {
-                        ContentBlock::ToolUse {id, name, ..} => yield LLMMessageChunk::ToolCallStart{ id: id.to_string(), name: name.to_string() },
-                        _ => {}
-                    }
-                },
-                StreamEvent::ContentBlockDelta { delta, .. } => {
+                StreamEvent::MessageStart { message } => message.usage.merge_into(&mut usage),
+                StreamEvent::MessageDelta { usage: Some(delta), .. } => delta.merge_into(&mut usage),
+                StreamEvent::ContentBlockStart { index, content_block: ContentBlock::ToolUse { id, name, .. } } => {
+                    yield LLMMessageChunk::tool_call_start(index, id.to_string(), name.to_string());
+                }
+                StreamEvent::ContentBlockDelta { index, delta } => {
                     match delta {
                         DeltaEvent::TextDelta { text } => yield LLMMessageChunk::Text(text),
-                        DeltaEvent::InputJsonDelta { partial_json } => yield LLMMessageChunk::ToolCallArgument(partial_json)
+                        DeltaEvent::InputJsonDelta { partial_json } => yield LLMMessageChunk::tool_call_argument(index, partial_json)
                     }
                 }
-                StreamEvent::ContentBlockStop { .. } => yield LLMMessageChunk::ContentBlockStop,
-                StreamEvent::MessageStop => yield LLMMessageChunk::stop(),
-                StreamEvent::MessageDelta { .. } => continue,
-                _ => continue,
+                StreamEvent::ContentBlockStop { index } => yield LLMMessageChunk::ContentBlockStop { index },
+                StreamEvent::MessageStop => {
+                    yield LLMMessageChunk::Usage(usage);
+                    yield LLMMessageChunk::stop();
+                }
+                StreamEvent::Error { error } => Err(LLMError::from(ProviderError::from(error)))?,
+                _ => {}
             }
         }
     }
@@ -161,17 +195,7 @@ impl LLMClient for ClaudeClient {
         messages: &[LLMMessage],
         tools: Option<&[LLMToolDefinition]>,
     ) -> Result<Vec<LLMMessage>, LLMError> {
-        let model = self.config.get_model();
-        let max_tokens = self.config.get_max_tokens();
-
-        let claude_messages: Vec<Message> = messages.iter().map(Message::from).collect();
-        let mut request = ChatCompletionRequest::new(model, max_tokens, claude_messages);
-
-        if let Some(tools) = tools {
-            // TODO: Convert to Claude tool without cloning.
-            let claude_tools: Vec<Tool> = tools.iter().map(Tool::from).collect();
-            request = request.with_tools(claude_tools);
-        }
+        let request = self.build_request(messages, tools);
 
         // Make API call
         let response = self.request_chat_completion(request, false).await?;
@@ -204,21 +228,8 @@ impl LLMClient for ClaudeClient {
         messages: &[LLMMessage],
         tools: Option<&[LLMToolDefinition]>,
     ) -> Result<BoxStream, LLMError> {
-        let model = self.config.get_model();
-        let max_tokens = self.config.get_max_tokens();
-
-        // Convert LLM messages to Claude messages
-        let claude_messages: Vec<Message> = messages.iter().map(Message::from).collect();
-
         // Build request with streaming enabled
-        let mut request =
-            ChatCompletionRequest::new(model, max_tokens, claude_messages).with_stream(true);
-
-        // Add tools if provided
-        if let Some(tools) = tools {
-            let claude_tools: Vec<Tool> = tools.iter().map(Tool::from).collect();
-            request = request.with_tools(claude_tools);
-        }
+        let request = self.build_request(messages, tools).with_stream(true);
 
         // Make streaming API call
         let response = self.request_chat_completion(request, true).await?;
@@ -226,33 +237,61 @@ impl LLMClient for ClaudeClient {
         let stream = Self::process_stream(response.events());
         Ok(events_to_messages(stream).boxed())
     }
+
+    async fn query_structured(
+        &self,
+        messages: &[LLMMessage],
+        schema: &LLMToolDefinition,
+    ) -> Result<Value, LLMError> {
+        let request = self.build_structured_request(messages, schema);
+
+        let response = self.request_chat_completion(request, false).await?;
+        let message_response: MessageResponse = response.json().await.map_err(|e| {
+            LLMError::ResponseFormat(format!("Failed to parse Claude response: {e}"))
+        })?;
+
+        message_response
+            .content
+            .into_iter()
+            .find_map(|block| match block {
+                ContentBlock::ToolUse { name, input, .. } if name == schema.name => {
+                    Some(input.into_owned())
+                }
+                _ => None,
+            })
+            .ok_or_else(|| {
+                LLMError::ResponseFormat(format!("Claude did not call the tool '{}'", schema.name))
+            })
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::core::{Config, Provider, ProviderConfig};
+    use crate::core::{Config, Profile, Provider};
     use crate::providers::Message as LLMMessage;
     use once_cell::sync::OnceCell;
+    use std::collections::BTreeMap;
 
     static CLIENT: OnceCell<ClaudeClient> = OnceCell::new();
     static CONFIG: OnceCell<Config> = OnceCell::new();
 
     fn get_test_config() -> &'static Config {
         CONFIG.get_or_init(|| Config {
-            provider: Provider::Claude,
+            profile: "claude".to_string(),
+            profiles: BTreeMap::from([(
+                "claude".to_string(),
+                Profile {
+                    backend: Provider::Claude,
+                    model: "claude-3-5-haiku-20241022".to_string(),
+                    max_tokens: 1024,
+                    ..Profile::default()
+                },
+            )]),
             system_prompt: None,
-            claude: ProviderConfig {
-                default_model: "claude-3-5-haiku-20241022".to_string(),
-                max_tokens: 1024,
-            },
-            openai: ProviderConfig {
-                default_model: "gpt-4o-mini".to_string(),
-                max_tokens: 1024,
-            },
             enable_tools: false,
             max_steps: 10,
-            theme: None,
+            ..Config::default()
         })
     }
 
@@ -264,6 +303,119 @@ mod tests {
         })
     }
 
+    #[test]
+    fn test_claude_request_includes_system_prompt_and_tools() {
+        let config = Config {
+            system_prompt: Some("You are a shell expert.".to_string()),
+            ..get_test_config().clone()
+        };
+        let client = ClaudeClient::new("test_key".to_string(), config);
+        let messages = vec![LLMMessage::user("List files")];
+        let tools = vec![LLMToolDefinition {
+            name: "execute_command".to_string(),
+            description: "Executes a command".to_string(),
+            parameters: serde_json::json!({ "type": "object" }),
+        }];
+
+        let request = serde_json::to_value(client.build_request(&messages, Some(&tools)))
+            .expect("Request should serialize");
+
+        assert_eq!(request["system"], "You are a shell expert.");
+        assert_eq!(request["messages"][0]["role"], "user");
+        assert_eq!(request["tools"][0]["name"], "execute_command");
+    }
+
+    #[test]
+    fn test_claude_structured_request_requires_tool() {
+        let client = ClaudeClient::new("test_key".to_string(), get_test_config().clone());
+        let messages = vec![LLMMessage::user("List files")];
+        let schema = LLMToolDefinition {
+            name: "suggest_command".to_string(),
+            description: "Suggests a command".to_string(),
+            parameters: serde_json::json!({ "type": "object" }),
+        };
+
+        let request = serde_json::to_value(client.build_structured_request(&messages, &schema))
+            .expect("Request should serialize");
+
+        assert_eq!(request["tools"][0]["name"], "suggest_command");
+        assert_eq!(
+            request["tool_choice"],
+            serde_json::json!({ "type": "tool", "name": "suggest_command" })
+        );
+    }
+
+    #[test]
+    fn test_claude_request_marks_tool_errors() {
+        let client = ClaudeClient::new("test_key".to_string(), get_test_config().clone());
+        let messages = vec![
+            LLMMessage::tool(serde_json::json!("ok"), "call_1"),
+            LLMMessage::tool_error("Tool not found: missing", "call_2"),
+        ];
+
+        let request = serde_json::to_value(client.build_request(&messages, None))
+            .expect("Request should serialize");
+
+        assert!(request["messages"][0]["content"][0]
+            .get("is_error")
+            .is_none());
+        let error = &request["messages"][1]["content"][0];
+        assert_eq!(error["type"], "tool_result");
+        assert_eq!(error["is_error"], true);
+        assert_eq!(error["content"], "Tool not found: missing");
+    }
+
+    #[test]
+    fn test_claude_request_without_system_prompt() {
+        let client = ClaudeClient::new("test_key".to_string(), get_test_config().clone());
+        let messages = vec![LLMMessage::user("List files")];
+
+        let request = serde_json::to_value(client.build_request(&messages, None))
+            .expect("Request should serialize");
+
+        assert!(request.get("system").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_stream_reports_usage_before_stop() {
+        let events = [
+            r#"{"type":"message_start","message":{"id":"msg_1","type":"message","role":"assistant","content":[],"model":"claude-3-5-haiku-20241022","stop_reason":null,"stop_sequence":null,"usage":{"input_tokens":25,"output_tokens":1,"cache_read_input_tokens":100,"cache_creation_input_tokens":0}}}"#,
+            r#"{"type":"content_block_delta","index":0,"delta":{"type":"text_delta","text":"Hi"}}"#,
+            r#"{"type":"message_delta","delta":{"stop_reason":"end_turn","stop_sequence":null},"usage":{"output_tokens":15}}"#,
+            r#"{"type":"message_stop"}"#,
+        ];
+        let stream = futures::stream::iter(
+            events.map(|event| Ok(serde_json::from_str::<StreamEvent>(event).unwrap())),
+        );
+
+        let chunks: Vec<_> = events_to_messages(stream).collect().await;
+
+        assert!(matches!(&chunks[0], Ok(LLMMessageChunk::Text(text)) if text == "Hi"));
+        assert!(matches!(
+            chunks[1],
+            Ok(LLMMessageChunk::Usage(LLMUsage {
+                input_tokens: 25,
+                output_tokens: 15,
+                cache_read_tokens: 100,
+                cache_creation_tokens: 0,
+            }))
+        ));
+        assert!(matches!(chunks[2], Ok(LLMMessageChunk::End(_))));
+    }
+
+    #[tokio::test]
+    async fn test_stream_error_event() {
+        let events =
+            [r#"{"type":"error","error":{"type":"overloaded_error","message":"Overloaded"}}"#];
+        let stream = futures::stream::iter(
+            events.map(|event| Ok(serde_json::from_str::<StreamEvent>(event).unwrap())),
+        );
+
+        let chunks: Vec<_> = events_to_messages(stream).collect().await;
+
+        assert!(matches!(chunks[..], [Err(LLMError::Overloaded(_))]));
+    }
+
     #[tokio::test]
     async fn test_claude_send_message_invalid_key() {
         let config = get_test_config().clone();
@@ -275,17 +427,13 @@ mod tests {
         let response = client.query(&messages, None).await;
 
         match response {
-            Err(LLMError::ApiError(ref error_msg)) => {
-                println!("Detailed API Error: {error_msg}");
-                assert!(
-                    error_msg.to_lowercase().contains("invalid")
-                        || error_msg.to_lowercase().contains("unauthorized"),
-                    "Expected error message to indicate invalid key, got: {error_msg}"
-                );
+            Err(LLMError::Authentication(error)) => {
+                assert_eq!(error.status, Some(401));
+                assert_eq!(error.error_type.as_deref(), Some("authentication_error"));
             }
             Err(other_error) => {
                 panic!(
-                    "Expected ApiError for invalid key, but got a different error type: {other_error:?}"
+                    "Expected Authentication error for invalid key, but got a different error type: {other_error:?}"
                 );
             }
             Ok(_) => panic!("Unexpected successful response with invalid key"),
@@ -303,17 +451,13 @@ mod tests {
         let stream_result = client.query_streaming(&messages, None).await;
 
         match stream_result {
-            Err(LLMError::ApiError(ref error_msg)) => {
-                println!("Detailed Streaming API Error: {error_msg}");
-                assert!(
-                    error_msg.to_lowercase().contains("invalid")
-                        || error_msg.to_lowercase().contains("unauthorized"),
-                    "Expected error message to indicate invalid key, got: {error_msg}"
-                );
+            Err(LLMError::Authentication(error)) => {
+                assert_eq!(error.status, Some(401));
+                assert_eq!(error.error_type.as_deref(), Some("authentication_error"));
             }
             Err(other_error) => {
                 panic!(
-                    "Expected ApiError for invalid key in streaming, but got a different error type: {other_error:?}"
+                    "Expected Authentication error for invalid key in streaming, but got a different error type: {other_error:?}"
                 );
             }
             Ok(_) => panic!("Unexpected successful streaming response with invalid key"),
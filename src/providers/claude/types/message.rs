@@ -1,6 +1,7 @@
 use std::borrow::Cow;
 
 use crate::providers::types::messages::Message as LLMMessage;
+use crate::providers::Usage as LLMUsage;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
@@ -34,6 +35,8 @@ pub enum ContentBlock<'a> {
     ToolResult {
         tool_use_id: Cow<'a, str>,
         content: Cow<'a, Value>,
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        is_error: bool,
     },
 }
 
@@ -108,6 +111,28 @@ pub struct Usage {
     pub cache_creation_input_tokens: Option<i32>,
 }
 
+impl Usage {
+    /// Overwrites the counts present in this usage report.
+    ///
+    /// Streamed `message_delta` events report cumulative counts, so later
+    /// reports replace earlier ones instead of being added up.
+    pub fn merge_into(&self, usage: &mut LLMUsage) {
+        let tokens = |count: i32| u64::from(count.unsigned_abs());
+        if let Some(count) = self.input_tokens {
+            usage.input_tokens = tokens(count);
+        }
+        if let Some(count) = self.output_tokens {
+            usage.output_tokens = tokens(count);
+        }
+        if let Some(count) = self.cache_read_input_tokens {
+            usage.cache_read_tokens = tokens(count);
+        }
+        if let Some(count) = self.cache_creation_input_tokens {
+            usage.cache_creation_tokens = tokens(count);
+        }
+    }
+}
+
 impl<'a> From<&'a LLMMessage> for Message<'a> {
     fn from(msg: &'a LLMMessage) -> Self {
         match msg {
@@ -115,10 +140,12 @@ impl<'a> From<&'a LLMMessage> for Message<'a> {
             LLMMessage::ToolResult {
                 content,
                 tool_call_id,
+                is_error,
             } => Self::User {
                 content: MessageContent::Array(vec![ContentBlock::ToolResult {
                     tool_use_id: tool_call_id.into(),
                     content: Cow::Borrowed(content),
+                    is_error: *is_error,
                 }]),
             },
             LLMMessage::Assistant {
@@ -8,4 +8,4 @@ pub use message::{
 
 pub use request::{ChatCompletionRequest, Metadata, Tool, ToolChoice};
 
-pub use stream::{DeltaEvent, MessageDeltaEvent, StreamError, StreamEvent};
+pub use stream::{DeltaEvent, ErrorResponse, MessageDeltaEvent, StreamError, StreamEvent};
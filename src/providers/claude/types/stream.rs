@@ -1,4 +1,5 @@
 use super::{ContentBlock, MessageResponse, StopReason, Usage};
+use crate::core::ProviderError;
 use serde::Deserialize;
 
 #[derive(Debug, Deserialize)]
@@ -43,9 +44,31 @@ pub struct MessageDeltaEvent {
     pub stop_sequence: Option<String>,
 }
 
+/// Error returned by the API, either as response body or as stream event
 #[derive(Debug, Deserialize)]
 pub struct StreamError {
     #[serde(rename = "type")]
     pub error_type: String,
     pub message: String,
 }
+
+/// Body of an error response
+#[derive(Debug, Deserialize)]
+pub struct ErrorResponse {
+    pub error: StreamError,
+}
+
+impl From<StreamError> for ProviderError {
+    fn from(error: StreamError) -> Self {
+        Self {
+            error_type: Some(error.error_type),
+            ..Self::new(None, error.message)
+        }
+    }
+}
+
+impl From<ErrorResponse> for ProviderError {
+    fn from(response: ErrorResponse) -> Self {
+        response.error.into()
+    }
+}
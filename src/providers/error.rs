@@ -0,0 +1,145 @@
+use std::time::Duration;
+
+use reqwest::{header::HeaderMap, Response};
+use serde::de::DeserializeOwned;
+
+use crate::core::{LLMError, ProviderError};
+
+/// Headers providers use to return the id of a request
+const REQUEST_ID_HEADERS: [&str; 2] = ["request-id", "x-request-id"];
+
+/// Passes successful responses through and turns error responses into typed
+/// errors.
+///
+/// The body is parsed as the provider specific error response `E`. Bodies in
+/// any other format are kept as the error message.
+pub async fn check_response<E>(response: Response) -> Result<Response, LLMError>
+where
+    E: DeserializeOwned + Into<ProviderError>,
+{
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let headers = response.headers().clone();
+    let body = response.text().await.unwrap_or_default();
+    let message = if body.trim().is_empty() {
+        status
+            .canonical_reason()
+            .unwrap_or("Unknown error")
+            .to_string()
+    } else {
+        body
+    };
+
+    Err(parse_error::<E>(status.as_u16(), &headers, message).into())
+}
+
+/// Builds a provider error from the status, headers and body of a response
+fn parse_error<E>(status: u16, headers: &HeaderMap, body: String) -> ProviderError
+where
+    E: DeserializeOwned + Into<ProviderError>,
+{
+    let error = serde_json::from_str::<E>(&body)
+        .map_or_else(|_| ProviderError::new(None, body), Into::into);
+
+    ProviderError {
+        status: Some(status),
+        request_id: REQUEST_ID_HEADERS
+            .iter()
+            .find_map(|name| headers.get(*name)?.to_str().ok())
+            .map(String::from),
+        retry_after: retry_after(headers),
+        ..error
+    }
+}
+
+/// Reads the delay requested by the provider.
+///
+/// Supports `retry-after-ms` as sent by OpenAI and `retry-after` in seconds.
+/// HTTP dates are not supported and fall back to the regular backoff.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let header = |name: &str| headers.get(name)?.to_str().ok()?.trim().parse::<f64>().ok();
+
+    header("retry-after-ms")
+        .map(|millis| millis / 1000.0)
+        .or_else(|| header("retry-after"))
+        .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::claude::types::ErrorResponse as ClaudeErrorResponse;
+    use crate::providers::openai::types::OpenAIErrorResponse;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn test_parse_claude_error() {
+        let mut headers = HeaderMap::new();
+        headers.insert("request-id", HeaderValue::from_static("req_011"));
+        headers.insert("retry-after", HeaderValue::from_static("3"));
+        let body = r#"{"type":"error","error":{"type":"rate_limit_error","message":"Number of requests has exceeded your rate limit"}}"#;
+
+        let error = parse_error::<ClaudeErrorResponse>(429, &headers, body.to_string());
+
+        assert_eq!(error.status, Some(429));
+        assert_eq!(error.error_type.as_deref(), Some("rate_limit_error"));
+        assert_eq!(
+            error.message,
+            "Number of requests has exceeded your rate limit"
+        );
+        assert_eq!(error.request_id.as_deref(), Some("req_011"));
+        assert_eq!(error.retry_after, Some(Duration::from_secs(3)));
+        assert!(matches!(LLMError::from(error), LLMError::RateLimited(_)));
+    }
+
+    #[test]
+    fn test_parse_openai_error() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-request-id", HeaderValue::from_static("req_abc"));
+        let body = r#"{"error":{"message":"This model's maximum context length is 128000 tokens.","type":"invalid_request_error","param":"messages","code":"context_length_exceeded"}}"#;
+
+        let error = parse_error::<OpenAIErrorResponse>(400, &headers, body.to_string());
+
+        assert_eq!(error.code.as_deref(), Some("context_length_exceeded"));
+        assert_eq!(error.request_id.as_deref(), Some("req_abc"));
+        assert!(matches!(
+            LLMError::from(error),
+            LLMError::ContextLengthExceeded(_)
+        ));
+    }
+
+    #[test]
+    fn test_parse_unknown_error_body() {
+        let error = parse_error::<OpenAIErrorResponse>(
+            502,
+            &HeaderMap::new(),
+            "<html>Bad Gateway</html>".to_string(),
+        );
+
+        assert_eq!(error.message, "<html>Bad Gateway</html>");
+        assert_eq!(error.error_type, None);
+        assert!(matches!(LLMError::from(error), LLMError::ServerError(_)));
+    }
+
+    #[test]
+    fn test_retry_after_headers() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+
+        headers.insert("retry-after", HeaderValue::from_static("2"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(2)));
+
+        headers.insert("retry-after-ms", HeaderValue::from_static("1500"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_millis(1500)));
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "retry-after",
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        assert_eq!(retry_after(&headers), None);
+    }
+}
@@ -3,6 +3,7 @@ use crate::providers::Message;
 use crate::tools::ToolDefinition;
 use async_trait::async_trait;
 use futures::Stream;
+use serde_json::Value;
 use std::pin::Pin;
 
 use super::MessageChunk;
@@ -24,4 +25,20 @@ pub trait LLMClient: Send + Sync {
         messages: &[Message],
         tools: Option<&[ToolDefinition]>,
     ) -> Result<BoxStream, LLMError>;
+
+    /// Query the LLM for structured output, returning the arguments of a
+    /// call of the given tool, which the model is required to make.
+    ///
+    /// Clients without support for forced tool calls keep the default, which
+    /// returns an error.
+    async fn query_structured(
+        &self,
+        _messages: &[Message],
+        schema: &ToolDefinition,
+    ) -> Result<Value, LLMError> {
+        Err(LLMError::ApiError(format!(
+            "Structured output ('{}') is not supported by this client",
+            schema.name
+        )))
+    }
 }
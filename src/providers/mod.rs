@@ -1,7 +1,10 @@
 pub mod claude;
+mod error;
 pub mod llm;
 pub mod openai;
+mod retry;
 pub mod types;
 
 pub use types::message_chunk::{FinishReason, MessageChunk};
 pub use types::messages::Message;
+pub use types::usage::Usage;
@@ -1,22 +1,34 @@
-use crate::core::{Config, LLMError};
+use std::collections::{BTreeMap, HashMap};
+
+use crate::core::{Config, LLMError, Provider};
 use crate::eventsource::{Event, EventSourceExt};
+use crate::providers::error::check_response;
 use crate::providers::llm::{BoxStream, LLMClient};
 use crate::providers::openai::types::message::FinishReason;
+use crate::providers::retry::send_with_retry;
 use crate::providers::Message as LLMMessage;
 use crate::providers::MessageChunk as LLMMessageChunk;
 use crate::tools::ToolDefinition as LLMToolDefinition;
 use async_stream::try_stream;
 use futures::{Stream, StreamExt};
-use reqwest::{Client, Response, StatusCode};
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    Client, Response,
+};
+use serde_json::Value;
 
 use super::types::{
-    ChatCompletionChunk, ChatCompletionObject, ChatCompletionRequest, Message, Tool,
+    ChatCompletionChunk, ChatCompletionObject, ChatCompletionRequest, Message, OpenAIErrorResponse,
+    StreamOptions, Tool, ToolChoice,
 };
 
-/// Constant for OpenAI Chat Completions API endpoint
-const API_URL: &str = "https://api.openai.com/v1/chat/completions";
+/// Base URL of the OpenAI API
+const API_BASE_URL: &str = "https://api.openai.com/v1";
+/// Path of the chat completions endpoint relative to the base URL
+const CHAT_COMPLETIONS_PATH: &str = "/chat/completions";
 
-/// Client for interacting with OpenAI's API
+/// Client for interacting with OpenAI's API or any endpoint compatible with
+/// its chat completions API
 ///
 /// Manages API key, configuration, and provides methods for sending messages
 /// and streaming responses.
@@ -24,19 +36,109 @@ pub struct OpenAIClient {
     api_key: String,
     client: Client,
     config: Config,
+    base_url: String,
+    headers: HeaderMap,
 }
 
 impl OpenAIClient {
     /// Creates a new OpenAI client with the given API key and configuration
     ///
     /// # Arguments
-    /// * `api_key` - Authentication token for OpenAI API
+    /// * `api_key` - Authentication token for OpenAI API, no `Authorization`
+    ///   header is sent if empty
     /// * `config` - Configuration settings for the client
     pub fn new(api_key: String, config: Config) -> Self {
         Self {
             api_key,
             client: Client::new(),
             config,
+            base_url: API_BASE_URL.to_string(),
+            headers: HeaderMap::new(),
+        }
+    }
+
+    /// Sends requests to a different OpenAI-compatible endpoint
+    ///
+    /// # Arguments
+    /// * `base_url` - Base URL of the API, e.g. `http://localhost:11434/v1`
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = base_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Adds extra headers sent with every request
+    ///
+    /// # Arguments
+    /// * `headers` - Header names mapped to their values
+    pub fn with_headers(mut self, headers: &HashMap<String, String>) -> Result<Self, LLMError> {
+        for (name, value) in headers {
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|e| LLMError::ConfigError(format!("Invalid header name '{name}': {e}")))?;
+            let value = HeaderValue::from_str(value).map_err(|e| {
+                LLMError::ConfigError(format!("Invalid value for header '{name}': {e}"))
+            })?;
+            self.headers.insert(name, value);
+        }
+        Ok(self)
+    }
+
+    /// Builds a chat completion request from the generic messages and tools,
+    /// prepending the configured system prompt as a system message
+    ///
+    /// # Arguments
+    /// * `messages` - The conversation so far
+    /// * `tools` - Optional tool definitions available to the model
+    /// * `stream` - Whether the response should be streamed
+    fn build_request<'a>(
+        &'a self,
+        messages: &'a [LLMMessage],
+        tools: Option<&'a [LLMToolDefinition]>,
+        stream: bool,
+    ) -> ChatCompletionRequest<'a> {
+        let system_message = self
+            .config
+            .get_system_prompt()
+            .map(|prompt| Message::system(prompt.into()));
+        let openai_messages: Vec<Message> = system_message
+            .into_iter()
+            .chain(messages.iter().map(Message::from))
+            .collect();
+
+        // Compatible servers generally only understand the older `max_tokens` field
+        let max_tokens = Some(self.config.get_max_tokens());
+        let (max_tokens, max_completion_tokens) = match self.config.get_provider() {
+            Provider::OpenAICompatible => (max_tokens, None),
+            _ => (None, max_tokens),
+        };
+        // Not every compatible server accepts `stream_options`, usage is still
+        // reported if they send it unasked
+        let include_usage = stream && self.config.get_provider() == Provider::OpenAI;
+
+        ChatCompletionRequest {
+            model: self.config.get_model(),
+            messages: openai_messages,
+            temperature: self.config.get_temperature(),
+            stream,
+            max_tokens,
+            max_completion_tokens,
+            tools: tools.map(|tools| tools.iter().map(Tool::from).collect()),
+            stream_options: include_usage.then_some(StreamOptions {
+                chunk_size: None,
+                include_usage: Some(true),
+            }),
+            ..Default::default()
+        }
+    }
+
+    /// Builds a request requiring the model to call the given function
+    fn build_structured_request<'a>(
+        &'a self,
+        messages: &'a [LLMMessage],
+        schema: &'a LLMToolDefinition,
+    ) -> ChatCompletionRequest<'a> {
+        ChatCompletionRequest {
+            tool_choice: Some(ToolChoice::function(schema.name.as_str())),
+            ..self.build_request(messages, Some(std::slice::from_ref(schema)), false)
         }
     }
 
@@ -51,30 +153,18 @@ impl OpenAIClient {
         &self,
         request: &'a ChatCompletionRequest<'a>,
     ) -> Result<Response, LLMError> {
-        let response = self
-            .client
-            .post(API_URL)
-            .header("Authorization", format!("Bearer {key}", key = self.api_key))
-            .json(request)
-            .send()
-            .await
-            .map_err(LLMError::from)?;
-
-        match response.status() {
-            StatusCode::OK => Ok(response),
-            StatusCode::UNAUTHORIZED => Err(LLMError::ApiError(
-                "Invalid API key or unauthorized access".to_string(),
-            )),
-            status => {
-                let error_text = response
-                    .text()
-                    .await
-                    .unwrap_or_else(|_| "Unknown error".to_string());
-                Err(LLMError::ApiError(format!(
-                    "API request failed with status {status}: {error_text}"
-                )))
+        send_with_retry(&self.config.retry, || {
+            let mut builder = self
+                .client
+                .post(format!("{}{CHAT_COMPLETIONS_PATH}", self.base_url))
+                .headers(self.headers.clone());
+            if !self.api_key.is_empty() {
+                builder = builder.bearer_auth(&self.api_key);
             }
-        }
+            let response = builder.json(request).send();
+            async move { check_response::<OpenAIErrorResponse>(response.await?).await }
+        })
+        .await
     }
 }
 
@@ -85,18 +175,7 @@ impl LLMClient for OpenAIClient {
         messages: &[LLMMessage],
         tools: Option<&[LLMToolDefinition]>,
     ) -> Result<Vec<LLMMessage>, LLMError> {
-        // Implement the method to ensure the future is Send
-        // Convert messages to OpenAI format upfront to ensure Send safety
-        let openai_messages: Vec<Message> = messages.iter().map(Message::from).collect();
-
-        let request = ChatCompletionRequest {
-            model: self.config.get_model(),
-            messages: openai_messages,
-            temperature: Some(0.7),
-            max_completion_tokens: Some(self.config.get_max_tokens()),
-            tools: tools.map(|tools| tools.iter().map(Tool::from).collect()),
-            ..Default::default()
-        };
+        let request = self.build_request(messages, tools, false);
 
         let response = self.create_chat_completion(&request).await?;
         let response_text = response
@@ -122,17 +201,7 @@ impl LLMClient for OpenAIClient {
         messages: &[LLMMessage],
         tools: Option<&[LLMToolDefinition]>,
     ) -> Result<BoxStream, LLMError> {
-        let openai_messages: Vec<Message> = messages.iter().map(Message::from).collect();
-
-        let request = ChatCompletionRequest {
-            model: self.config.get_model(),
-            messages: openai_messages,
-            temperature: Some(0.7),
-            stream: true,
-            max_completion_tokens: Some(self.config.get_max_tokens()),
-            tools: tools.map(|tools| tools.iter().map(Tool::from).collect()),
-            ..Default::default()
-        };
+        let request = self.build_request(messages, tools, true);
 
         let response = self.create_chat_completion(&request).await?;
         let stream = process_stream(response.events());
@@ -140,19 +209,75 @@ impl LLMClient for OpenAIClient {
 
         Ok(message_stream.boxed())
     }
+
+    async fn query_structured(
+        &self,
+        messages: &[LLMMessage],
+        schema: &LLMToolDefinition,
+    ) -> Result<Value, LLMError> {
+        let request = self.build_structured_request(messages, schema);
+
+        let response = self.create_chat_completion(&request).await?;
+        let response_text = response
+            .text()
+            .await
+            .map_err(|e| LLMError::ResponseFormat(format!("Failed to get response text: {e}")))?;
+        let chat_response: ChatCompletionObject =
+            serde_json::from_str(&response_text).map_err(|e| {
+                LLMError::ResponseFormat(format!("Failed to parse OpenAI response: {e}"))
+            })?;
+
+        let arguments = chat_response
+            .choices
+            .into_iter()
+            .filter_map(|choice| match LLMMessage::from(choice.message) {
+                LLMMessage::Assistant { tool_calls, .. } => tool_calls,
+                _ => None,
+            })
+            .flatten()
+            .find(|call| call.name == schema.name)
+            .map(|call| call.arguments)
+            .ok_or_else(|| {
+                LLMError::ResponseFormat(format!(
+                    "The model did not call the function '{}'",
+                    schema.name
+                ))
+            })?;
+        // The arguments are sent as a JSON encoded string
+        match arguments {
+            Value::String(arguments) => serde_json::from_str(&arguments).map_err(|e| {
+                LLMError::ResponseFormat(format!("Invalid arguments of '{}': {e}", schema.name))
+            }),
+            arguments => Ok(arguments),
+        }
+    }
 }
 
 fn events_to_messages(
     mut stream: impl Stream<Item = Result<ChatCompletionChunk, LLMError>> + Send + Unpin + 'static,
 ) -> impl Stream<Item = Result<LLMMessageChunk, LLMError>> + Send + 'static {
     try_stream! {
+        // The usage is sent in a separate chunk after the finish reason, so
+        // the end of the message is only signalled once the stream is done
+        let mut stopped = false;
+        // Ids of the tool calls started so far by their index
+        let mut tool_calls = BTreeMap::new();
         while let Some(chunk) = stream.next().await {
             let chunk = chunk?;
+            if let Some(usage) = chunk.usage {
+                yield LLMMessageChunk::Usage(usage.into());
+            }
             for choice in chunk.choices {
                 if let Some(finish_reason) = choice.finish_reason {
+                    // Some compatible servers finish tool calls with `stop`
+                    if matches!(finish_reason, FinishReason::Stop | FinishReason::ToolCalls) {
+                        for index in std::mem::take(&mut tool_calls).into_keys() {
+                            yield LLMMessageChunk::ContentBlockStop { index };
+                        }
+                    }
                     match finish_reason {
-                        FinishReason::Stop => yield LLMMessageChunk::stop(),
-                        FinishReason::ToolCalls => yield LLMMessageChunk::ContentBlockStop,
+                        FinishReason::Stop => stopped = true,
+                        FinishReason::ToolCalls => {}
                         FinishReason::Length => yield LLMMessageChunk::error(
                             "Response exceeded max tokens".to_string()
                         ),
@@ -165,19 +290,28 @@ fn events_to_messages(
                     if let Some(content) = delta.content {
                         yield LLMMessageChunk::Text(content);
                     }
-                    if let Some(tool_calls) = delta.tool_calls {
-                        for tool_call in tool_calls {
+                    if let Some(tool_calls_delta) = delta.tool_calls {
+                        for tool_call in tool_calls_delta {
+                            let index = tool_call.index;
                             if let (Some(id), Some(name)) = (tool_call.id, tool_call.function.name) {
-                                yield LLMMessageChunk::ToolCallStart { id, name };
+                                // Servers without indices send every call at index 0
+                                if tool_calls.get(&index).is_some_and(|started| started != &id) {
+                                    yield LLMMessageChunk::ContentBlockStop { index };
+                                }
+                                tool_calls.insert(index, id.clone());
+                                yield LLMMessageChunk::tool_call_start(index, id, name);
                             }
                             if !tool_call.function.arguments.is_empty() {
-                                yield LLMMessageChunk::ToolCallArgument(tool_call.function.arguments);
+                                yield LLMMessageChunk::tool_call_argument(index, tool_call.function.arguments);
                             }
                         }
                     }
                 }
             }
         }
+        if stopped {
+            yield LLMMessageChunk::stop();
+        }
     }
 }
 
@@ -216,27 +350,43 @@ impl TryFrom<Event> for ChatCompletionChunk {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::core::{Config, Provider, ProviderConfig};
+    use crate::core::{Config, Profile, Provider};
+    use crate::providers::Usage as LLMUsage;
     use once_cell::sync::OnceCell;
+    use std::collections::BTreeMap;
 
     static CLIENT: OnceCell<OpenAIClient> = OnceCell::new();
     static CONFIG: OnceCell<Config> = OnceCell::new();
 
     fn get_test_config() -> &'static Config {
         CONFIG.get_or_init(|| Config {
-            provider: Provider::OpenAI,
+            profile: String::from("openai"),
+            profiles: BTreeMap::from([
+                (
+                    String::from("openai"),
+                    Profile {
+                        backend: Provider::OpenAI,
+                        model: String::from("gpt-4"),
+                        max_tokens: 1024,
+                        temperature: Some(0.7),
+                        ..Profile::default()
+                    },
+                ),
+                (
+                    String::from("local"),
+                    Profile {
+                        backend: Provider::OpenAICompatible,
+                        model: String::from("llama3.2"),
+                        max_tokens: 1024,
+                        base_url: Some(String::from("http://localhost:11434/v1")),
+                        ..Profile::default()
+                    },
+                ),
+            ]),
             system_prompt: None,
-            claude: ProviderConfig {
-                default_model: String::from("claude-3-5-haiku-20241022"),
-                max_tokens: 1024,
-            },
-            openai: ProviderConfig {
-                default_model: String::from("gpt-4"),
-                max_tokens: 1024,
-            },
             enable_tools: false,
             max_steps: 10,
-            theme: None,
+            ..Config::default()
         })
     }
 
@@ -248,6 +398,243 @@ mod tests {
         })
     }
 
+    #[test]
+    fn test_openai_request_prepends_system_prompt() {
+        let config = Config {
+            system_prompt: Some("You are a shell expert.".to_string()),
+            ..get_test_config().clone()
+        };
+        let client = OpenAIClient::new(String::from("test_key"), config);
+        let messages = vec![LLMMessage::user("List files")];
+        let tools = vec![LLMToolDefinition {
+            name: String::from("execute_command"),
+            description: String::from("Executes a command"),
+            parameters: serde_json::json!({ "type": "object" }),
+        }];
+
+        let request = serde_json::to_value(client.build_request(&messages, Some(&tools), true))
+            .expect("Request should serialize");
+
+        assert_eq!(request["messages"][0]["role"], "system");
+        assert_eq!(request["messages"][0]["content"], "You are a shell expert.");
+        assert_eq!(request["messages"][1]["role"], "user");
+        assert_eq!(request["tools"][0]["function"]["name"], "execute_command");
+        assert_eq!(request["stream"], true);
+    }
+
+    #[test]
+    fn test_openai_structured_request_requires_function() {
+        let client = OpenAIClient::new(String::from("test_key"), get_test_config().clone());
+        let messages = vec![LLMMessage::user("List files")];
+        let schema = LLMToolDefinition {
+            name: String::from("suggest_command"),
+            description: String::from("Suggests a command"),
+            parameters: serde_json::json!({ "type": "object" }),
+        };
+
+        let request = serde_json::to_value(client.build_structured_request(&messages, &schema))
+            .expect("Request should serialize");
+
+        assert_eq!(request["tools"][0]["function"]["name"], "suggest_command");
+        assert_eq!(
+            request["tool_choice"],
+            serde_json::json!({ "type": "function", "function": { "name": "suggest_command" } })
+        );
+        assert_eq!(request["stream"], false);
+    }
+
+    #[test]
+    fn test_response_with_only_tool_calls() {
+        let response = r#"{"id":"1","object":"chat.completion","created":0,"model":"gpt-4o","choices":[{"index":0,"finish_reason":"tool_calls","message":{"role":"assistant","content":null,"tool_calls":[{"id":"call_1","type":"function","function":{"name":"suggest_command","arguments":"{\"command\":\"ls\"}"}}]}}],"usage":{"prompt_tokens":9,"completion_tokens":3,"total_tokens":12}}"#;
+
+        let response: ChatCompletionObject = serde_json::from_str(response).unwrap();
+        let message = response.choices.into_iter().next().unwrap().message;
+
+        assert!(matches!(
+            LLMMessage::from(message),
+            LLMMessage::Assistant { content, tool_calls: Some(calls) }
+                if content.is_empty() && calls[0].name == "suggest_command"
+        ));
+    }
+
+    #[test]
+    fn test_openai_request_without_system_prompt() {
+        let client = OpenAIClient::new(String::from("test_key"), get_test_config().clone());
+        let messages = vec![LLMMessage::user("List files")];
+
+        let request = serde_json::to_value(client.build_request(&messages, None, false))
+            .expect("Request should serialize");
+
+        assert_eq!(request["messages"].as_array().map(Vec::len), Some(1));
+        assert_eq!(request["messages"][0]["role"], "user");
+    }
+
+    #[test]
+    fn test_openai_request_marks_tool_errors() {
+        let client = OpenAIClient::new(String::from("test_key"), get_test_config().clone());
+        let messages = vec![LLMMessage::tool_error("Tool not found: missing", "call_1")];
+
+        let request = serde_json::to_value(client.build_request(&messages, None, false))
+            .expect("Request should serialize");
+
+        assert_eq!(request["messages"][0]["role"], "tool");
+        assert_eq!(request["messages"][0]["tool_call_id"], "call_1");
+        assert_eq!(
+            request["messages"][0]["content"],
+            "Error: Tool not found: missing"
+        );
+    }
+
+    #[test]
+    fn test_compatible_request_uses_max_tokens() {
+        let config = Config {
+            profile: String::from("local"),
+            ..get_test_config().clone()
+        };
+        let client =
+            OpenAIClient::new(String::new(), config).with_base_url("http://localhost:11434/v1/");
+        let messages = vec![LLMMessage::user("List files")];
+
+        let request = serde_json::to_value(client.build_request(&messages, None, true))
+            .expect("Request should serialize");
+
+        assert_eq!(client.base_url, "http://localhost:11434/v1");
+        assert_eq!(request["model"], "llama3.2");
+        assert_eq!(request["max_tokens"], 1024);
+        assert!(request.get("max_completion_tokens").is_none());
+    }
+
+    #[test]
+    fn test_openai_stream_requests_usage() {
+        let client = OpenAIClient::new(String::from("test_key"), get_test_config().clone());
+        let messages = vec![LLMMessage::user("List files")];
+
+        let request = serde_json::to_value(client.build_request(&messages, None, true))
+            .expect("Request should serialize");
+        assert_eq!(request["stream_options"]["include_usage"], true);
+
+        let request = serde_json::to_value(client.build_request(&messages, None, false))
+            .expect("Request should serialize");
+        assert!(request.get("stream_options").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_stream_reports_usage_before_stop() {
+        let chunks = [
+            r#"{"id":"1","object":"chat.completion.chunk","created":0,"model":"gpt-4o","choices":[{"index":0,"delta":{"content":"Hi"}}]}"#,
+            r#"{"id":"1","object":"chat.completion.chunk","created":0,"model":"gpt-4o","choices":[{"index":0,"delta":{},"finish_reason":"stop"}]}"#,
+            r#"{"id":"1","object":"chat.completion.chunk","created":0,"model":"gpt-4o","choices":[],"usage":{"prompt_tokens":30,"completion_tokens":5,"total_tokens":35,"prompt_tokens_details":{"cached_tokens":20}}}"#,
+        ];
+        let stream = futures::stream::iter(
+            chunks.map(|chunk| Ok(serde_json::from_str::<ChatCompletionChunk>(chunk).unwrap())),
+        );
+
+        let chunks: Vec<_> = events_to_messages(stream).collect().await;
+
+        assert!(matches!(&chunks[0], Ok(LLMMessageChunk::Text(text)) if text == "Hi"));
+        assert!(matches!(
+            chunks[1],
+            Ok(LLMMessageChunk::Usage(LLMUsage {
+                input_tokens: 10,
+                output_tokens: 5,
+                cache_read_tokens: 20,
+                cache_creation_tokens: 0,
+            }))
+        ));
+        assert!(matches!(chunks[2], Ok(LLMMessageChunk::End(_))));
+        assert_eq!(chunks.len(), 3);
+    }
+
+    /// Streams the given choice deltas and describes the resulting tool call chunks
+    async fn tool_call_chunks(choices: &[&str]) -> Vec<String> {
+        let chunks: Vec<_> = choices
+            .iter()
+            .map(|choice| {
+                let chunk = format!(
+                    r#"{{"id":"1","object":"chat.completion.chunk","created":0,"model":"gpt-4o","choices":[{choice}]}}"#
+                );
+                Ok(serde_json::from_str::<ChatCompletionChunk>(&chunk).unwrap())
+            })
+            .collect();
+        let chunks: Vec<_> = events_to_messages(futures::stream::iter(chunks))
+            .collect()
+            .await;
+
+        chunks
+            .into_iter()
+            .filter_map(|chunk| match chunk.unwrap() {
+                LLMMessageChunk::ToolCallStart { index, id, name } => {
+                    Some(format!("start {index} {id} {name}"))
+                }
+                LLMMessageChunk::ToolCallArgument { index, arguments } => {
+                    Some(format!("args {index} {arguments}"))
+                }
+                LLMMessageChunk::ContentBlockStop { index } => Some(format!("stop {index}")),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_stream_parallel_tool_calls() {
+        let chunks = tool_call_chunks(&[
+            r#"{"index":0,"delta":{"tool_calls":[{"index":0,"id":"call_a","type":"function","function":{"name":"execute_command","arguments":""}}]}}"#,
+            r#"{"index":0,"delta":{"tool_calls":[{"index":0,"function":{"arguments":"{\"command\":"}}]}}"#,
+            r#"{"index":0,"delta":{"tool_calls":[{"index":1,"id":"call_b","type":"function","function":{"name":"execute_command","arguments":"{\"command\":\"pwd\"}"}}]}}"#,
+            r#"{"index":0,"delta":{"tool_calls":[{"index":0,"function":{"arguments":"\"ls\"}"}}]}}"#,
+            r#"{"index":0,"delta":{},"finish_reason":"tool_calls"}"#,
+        ])
+        .await;
+
+        assert_eq!(
+            chunks,
+            [
+                "start 0 call_a execute_command",
+                r#"args 0 {"command":"#,
+                "start 1 call_b execute_command",
+                r#"args 1 {"command":"pwd"}"#,
+                r#"args 0 "ls"}"#,
+                "stop 0",
+                "stop 1",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stream_tool_calls_without_index() {
+        let chunks = tool_call_chunks(&[
+            r#"{"index":0,"delta":{"tool_calls":[{"id":"call_a","function":{"name":"read","arguments":"{}"}},{"id":"call_b","function":{"name":"list","arguments":"{}"}}]}}"#,
+            r#"{"index":0,"delta":{},"finish_reason":"stop"}"#,
+        ])
+        .await;
+
+        assert_eq!(
+            chunks,
+            [
+                "start 0 call_a read",
+                "args 0 {}",
+                "stop 0",
+                "start 0 call_b list",
+                "args 0 {}",
+                "stop 0",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_with_headers() {
+        let headers = HashMap::from([(String::from("X-Title"), String::from("llm-cli"))]);
+        let client = OpenAIClient::new(String::new(), get_test_config().clone())
+            .with_headers(&headers)
+            .expect("Headers should be valid");
+        assert_eq!(client.headers["x-title"], "llm-cli");
+
+        let invalid = HashMap::from([(String::from("bad header"), String::from("value"))]);
+        let result =
+            OpenAIClient::new(String::new(), get_test_config().clone()).with_headers(&invalid);
+        assert!(matches!(result, Err(LLMError::ConfigError(_))));
+    }
+
     #[tokio::test]
     async fn test_openai_send_message() {
         let messages = vec![LLMMessage::User {
@@ -314,7 +701,7 @@ mod tests {
         let system_msg = String::from("You are a helpful assistant.");
         let user_msg = String::from("What's the weather like?");
         let request = ChatCompletionRequest {
-            model: &get_test_config().openai.default_model,
+            model: get_test_config().get_model(),
             messages: vec![
                 Message::System {
                     content: system_msg.into(),
@@ -326,7 +713,7 @@ mod tests {
                 },
             ],
             temperature: Some(0.7),
-            max_completion_tokens: Some(get_test_config().openai.max_tokens),
+            max_completion_tokens: Some(get_test_config().get_max_tokens()),
             ..Default::default()
         };
 
@@ -378,13 +765,11 @@ mod tests {
         assert!(response.is_err(), "Expected error with invalid API key");
 
         match response {
-            Err(LLMError::ApiError(msg)) => {
-                assert!(
-                    msg.contains("Invalid API key"),
-                    "Unexpected error message. Expected 'Invalid API key', got: '{msg}'"
-                );
+            Err(LLMError::Authentication(error)) => {
+                assert_eq!(error.status, Some(401));
+                assert_eq!(error.code.as_deref(), Some("invalid_api_key"));
             }
-            _ => panic!("Expected ApiError variant with invalid key"),
+            _ => panic!("Expected Authentication variant with invalid key"),
         }
     }
 
@@ -404,13 +789,11 @@ mod tests {
         );
 
         match stream_result {
-            Err(LLMError::ApiError(msg)) => {
-                assert!(
-                    msg.contains("Invalid API key"),
-                    "Unexpected error message. Expected 'Invalid API key', got: '{msg}'"
-                );
+            Err(LLMError::Authentication(error)) => {
+                assert_eq!(error.status, Some(401));
+                assert_eq!(error.code.as_deref(), Some("invalid_api_key"));
             }
-            _ => panic!("Expected ApiError variant with invalid key"),
+            _ => panic!("Expected Authentication variant with invalid key"),
         }
     }
 
@@ -422,7 +805,7 @@ mod tests {
         let user_msg2 = String::from("What's my name?");
 
         let request = ChatCompletionRequest {
-            model: &get_test_config().openai.default_model,
+            model: get_test_config().get_model(),
             messages: vec![
                 Message::System {
                     content: system_msg.into(),
@@ -444,7 +827,7 @@ mod tests {
                 },
             ],
             temperature: Some(0.7),
-            max_completion_tokens: Some(get_test_config().openai.max_tokens),
+            max_completion_tokens: Some(get_test_config().get_max_tokens()),
             ..Default::default()
         };
 
@@ -41,6 +41,10 @@ pub struct MessageChunk {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ToolCall {
+    /// Position of the call among parallel tool calls, some compatible
+    /// servers leave it out
+    #[serde(default)]
+    pub index: usize,
     pub id: Option<String>,
     #[serde(rename = "type")]
     pub call_type: Option<String>,
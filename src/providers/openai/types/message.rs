@@ -3,7 +3,7 @@ use std::borrow::Cow;
 use crate::providers::types::messages::Message as LLMMessage;
 use crate::tools::ToolCall as LLMToolCall;
 use crate::tools::ToolDefinition as LLMToolDefinition;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use serde_json::Value;
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -26,6 +26,8 @@ pub enum Message<'a> {
         name: Option<String>,
     },
     Assistant {
+        /// Null in responses that only call tools
+        #[serde(default, deserialize_with = "null_as_empty")]
         content: Cow<'a, str>,
         #[serde(skip_serializing_if = "Option::is_none")]
         name: Option<String>,
@@ -40,6 +42,12 @@ pub enum Message<'a> {
     },
 }
 
+/// Deserializes a string that may be null as an empty string
+fn null_as_empty<'de, 'a, D: Deserializer<'de>>(deserializer: D) -> Result<Cow<'a, str>, D::Error> {
+    Option::<String>::deserialize(deserializer)
+        .map(|content| Cow::Owned(content.unwrap_or_default()))
+}
+
 impl<'a> Message<'a> {
     pub const fn developer(content: Cow<'a, str>) -> Self {
         Self::Developer {
@@ -139,9 +147,30 @@ pub struct Function<'a> {
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum ToolChoice {
+    Mode(ToolChoiceMode),
+    Function {
+        #[serde(rename = "type")]
+        call_type: CallType,
+        function: FunctionChoice,
+    },
+}
+
+impl ToolChoice {
+    /// Requires the model to call the named function
+    pub fn function(name: impl Into<String>) -> Self {
+        Self::Function {
+            call_type: CallType::Function,
+            function: FunctionChoice { name: name.into() },
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolChoiceMode {
     None,
     Auto,
-    Function { function: FunctionChoice },
+    Required,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -161,6 +190,9 @@ pub struct ResponseFormat {
 pub struct StreamOptions {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub chunk_size: Option<u32>,
+    /// Request a final chunk with the token usage of the whole request
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub include_usage: Option<bool>,
 }
 
 impl<'a> From<Message<'a>> for LLMMessage {
@@ -194,6 +226,7 @@ impl<'a> From<Message<'a>> for LLMMessage {
             } => Self::ToolResult {
                 content: content.into_owned(),
                 tool_call_id: tool_call_id.to_string(),
+                is_error: false,
             },
         }
     }
@@ -211,10 +244,10 @@ impl<'a> From<&'a LLMMessage> for Message<'a> {
                     calls
                         .iter()
                         .map(|call| ToolCall {
-                            id: call.id.to_string(),
+                            id: call.id.clone(),
                             call_type: CallType::Function,
                             function: FunctionCall {
-                                name: call.name.to_string(),
+                                name: call.name.clone(),
                                 arguments: Value::String(call.arguments.to_string()),
                             },
                         })
@@ -222,9 +255,24 @@ impl<'a> From<&'a LLMMessage> for Message<'a> {
                 });
                 Self::assistant(content.into(), tool_calls)
             }
+            // Tool messages have no error flag, so failures are marked in the text
+            LLMMessage::ToolResult {
+                content,
+                tool_call_id,
+                is_error: true,
+            } => {
+                let error = content
+                    .as_str()
+                    .map_or_else(|| content.to_string(), str::to_string);
+                Self::tool(
+                    Cow::Owned(Value::String(format!("Error: {error}"))),
+                    tool_call_id,
+                )
+            }
             LLMMessage::ToolResult {
                 content,
                 tool_call_id,
+                is_error: false,
             } => Self::tool(Cow::Borrowed(content), tool_call_id),
         }
     }
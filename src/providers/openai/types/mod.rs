@@ -8,3 +8,4 @@ pub use chat_completion_chunk::ChatCompletionChunk;
 pub use chat_completion_object::ChatCompletionObject;
 pub use chat_completion_request::ChatCompletionRequest;
 pub use message::{Message, ResponseFormat, StreamOptions, Tool, ToolChoice};
+pub use shared::OpenAIErrorResponse;
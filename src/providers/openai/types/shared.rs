@@ -2,6 +2,9 @@
 
 use serde::{Deserialize, Serialize};
 
+use crate::core::ProviderError;
+use crate::providers::Usage as LLMUsage;
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct LogProbs {
     pub content: Option<Vec<TokenLogProb>>,
@@ -28,6 +31,29 @@ pub struct Usage {
     pub prompt_tokens: u32,
     pub completion_tokens: u32,
     pub total_tokens: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_tokens_details: Option<PromptTokensDetails>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PromptTokensDetails {
+    #[serde(default)]
+    pub cached_tokens: u32,
+}
+
+impl From<Usage> for LLMUsage {
+    /// Converts the usage, moving cached tokens out of the prompt tokens
+    fn from(usage: Usage) -> Self {
+        let cached_tokens = usage
+            .prompt_tokens_details
+            .map_or(0, |details| details.cached_tokens);
+        Self {
+            input_tokens: u64::from(usage.prompt_tokens.saturating_sub(cached_tokens)),
+            output_tokens: u64::from(usage.completion_tokens),
+            cache_read_tokens: u64::from(cached_tokens),
+            cache_creation_tokens: 0,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -42,3 +68,18 @@ pub struct OpenAIErrorDetails {
     pub error_type: String,
     pub code: Option<String>,
 }
+
+impl From<OpenAIErrorResponse> for ProviderError {
+    fn from(response: OpenAIErrorResponse) -> Self {
+        let OpenAIErrorDetails {
+            message,
+            error_type,
+            code,
+        } = response.error;
+        Self {
+            error_type: Some(error_type),
+            code,
+            ..Self::new(None, message)
+        }
+    }
+}
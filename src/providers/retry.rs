@@ -0,0 +1,189 @@
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::BuildHasher;
+use std::time::Duration;
+
+use crate::core::{LLMError, RetryConfig};
+
+/// Sends a request, retrying it on rate limits, overloaded or failing servers
+/// and connection errors.
+///
+/// The delay between attempts grows exponentially with jitter, unless the
+/// provider asks for a specific delay through a `retry-after` header. Both
+/// are capped at the configured maximum delay. Once all attempts are used up
+/// the last error is returned to the caller.
+///
+/// # Arguments
+/// * `config` - The retry settings
+/// * `send` - Sends a fresh request for every attempt and checks its response
+pub async fn send_with_retry<T, F, Fut>(config: &RetryConfig, mut send: F) -> Result<T, LLMError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, LLMError>>,
+{
+    let max_attempts = config.max_attempts.max(1);
+    let max_delay = Duration::from_millis(config.max_delay_ms);
+
+    for attempt in 1.. {
+        match send().await {
+            Err(e) if attempt < max_attempts && e.is_retryable() => {
+                let delay = e
+                    .retry_after()
+                    .unwrap_or_else(|| backoff_delay(config, attempt))
+                    .min(max_delay);
+                eprintln!(
+                    "{e}, retrying in {:.1}s (attempt {}/{max_attempts})",
+                    delay.as_secs_f64(),
+                    attempt + 1
+                );
+                tokio::time::sleep(delay).await;
+            }
+            result => return result,
+        }
+    }
+
+    unreachable!("the retry loop only ends by returning")
+}
+
+/// Computes the exponential backoff for the given attempt.
+///
+/// The delay doubles with every attempt up to the maximum delay, and a random
+/// jitter of up to half the delay spreads out retries of concurrent clients.
+fn backoff_delay(config: &RetryConfig, attempt: u32) -> Duration {
+    let delay = config
+        .initial_delay_ms
+        .saturating_mul(2u64.saturating_pow(attempt.saturating_sub(1)))
+        .min(config.max_delay_ms);
+    let jitter = RandomState::new().hash_one(attempt) % (delay / 2 + 1);
+
+    Duration::from_millis(delay - delay / 2 + jitter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::ProviderError;
+    use std::collections::VecDeque;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn test_config(max_attempts: u32) -> RetryConfig {
+        RetryConfig {
+            max_attempts,
+            initial_delay_ms: 0,
+            max_delay_ms: 1000,
+        }
+    }
+
+    fn error(status: u16) -> LLMError {
+        ProviderError::new(Some(status), "error").into()
+    }
+
+    /// Runs `send_with_retry` over scripted results and returns the result
+    /// together with the number of attempts made
+    async fn run_scripted(
+        max_attempts: u32,
+        results: Vec<Result<&'static str, LLMError>>,
+    ) -> (Result<&'static str, LLMError>, usize) {
+        let mut results = VecDeque::from(results);
+        let attempts = AtomicUsize::new(0);
+
+        let result = send_with_retry(&test_config(max_attempts), || {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            let result = results.pop_front().expect("Unexpected attempt");
+            async move { result }
+        })
+        .await;
+
+        (result, attempts.into_inner())
+    }
+
+    #[tokio::test]
+    async fn test_retries_until_success() {
+        let (result, attempts) =
+            run_scripted(3, vec![Err(error(429)), Err(error(529)), Ok("done")]).await;
+
+        assert_eq!(result.unwrap(), "done");
+        assert_eq!(attempts, 3);
+    }
+
+    #[tokio::test]
+    async fn test_returns_last_error_when_attempts_are_exhausted() {
+        let (result, attempts) = run_scripted(2, vec![Err(error(503)), Err(error(500))]).await;
+
+        assert!(matches!(
+            result,
+            Err(LLMError::ServerError(ProviderError {
+                status: Some(500),
+                ..
+            }))
+        ));
+        assert_eq!(attempts, 2);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_requests_are_not_retried() {
+        let (result, attempts) = run_scripted(3, vec![Err(error(400))]).await;
+
+        assert!(matches!(result, Err(LLMError::InvalidRequest(_))));
+        assert_eq!(attempts, 1);
+    }
+
+    #[tokio::test]
+    async fn test_connection_errors_are_retried() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+
+        let client = reqwest::Client::new();
+        let attempts = AtomicUsize::new(0);
+        let result = send_with_retry(&test_config(2), || {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            let request = client.get(&url).send();
+            async move { request.await.map_err(LLMError::from) }
+        })
+        .await;
+
+        assert!(matches!(result, Err(LLMError::Network(_))));
+        assert_eq!(attempts.into_inner(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_timeouts_are_not_retried() {
+        // Accepts connections but never responds
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(100))
+            .build()
+            .unwrap();
+        let attempts = AtomicUsize::new(0);
+        let result = send_with_retry(&test_config(3), || {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            let request = client.post(&url).send();
+            async move { request.await.map_err(LLMError::from) }
+        })
+        .await;
+
+        assert!(matches!(result, Err(LLMError::Network(ref e)) if e.is_timeout()));
+        assert_eq!(attempts.into_inner(), 1);
+    }
+
+    #[test]
+    fn test_backoff_grows_and_is_capped() {
+        let config = RetryConfig {
+            max_attempts: 10,
+            initial_delay_ms: 1000,
+            max_delay_ms: 5000,
+        };
+
+        for (attempt, delay) in [(1, 1000), (2, 2000), (3, 4000), (4, 5000), (8, 5000)] {
+            let backoff = backoff_delay(&config, attempt);
+            assert!(
+                backoff >= Duration::from_millis(delay / 2)
+                    && backoff <= Duration::from_millis(delay),
+                "attempt {attempt}: {backoff:?}"
+            );
+        }
+    }
+}
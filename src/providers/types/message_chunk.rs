@@ -1,23 +1,36 @@
+use serde::Serialize;
+
+use super::Usage;
+
 /// Represents a chunk of a streaming message from a provider
 /// This is a generic representation that both OpenAI and Claude chunks
 /// can be converted into
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
 pub enum MessageChunk {
     /// A chunk containing text content
     Text(String),
     /// The start of a text chunk
     TextStart,
-    /// Start of a tool call
-    ToolCallStart { id: String, name: String },
-    /// Content for a tool call's arguments (typically received in multiple chunks)
-    ToolCallArgument(String),
-    /// End of a tool call
-    ContentBlockStop,
+    /// Start of a tool call. Parallel tool calls are told apart by their index
+    ToolCallStart {
+        index: usize,
+        id: String,
+        name: String,
+    },
+    /// Content for the arguments of the tool call with the given index
+    /// (typically received in multiple chunks)
+    ToolCallArgument { index: usize, arguments: String },
+    /// End of the content block with the given index, e.g. a tool call
+    ContentBlockStop { index: usize },
+    /// Token usage of the request, sent once before the end of the stream
+    Usage(Usage),
     /// Stream end marker with optional finish reason
     End(FinishReason),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum FinishReason {
     /// The model finished generating content
     Stop,
@@ -27,8 +40,13 @@ pub enum FinishReason {
 
 impl MessageChunk {
     /// Create a new tool call start chunk
-    pub const fn tool_call_start(id: String, name: String) -> Self {
-        Self::ToolCallStart { id, name }
+    pub const fn tool_call_start(index: usize, id: String, name: String) -> Self {
+        Self::ToolCallStart { index, id, name }
+    }
+
+    /// Create a new chunk of tool call arguments
+    pub const fn tool_call_argument(index: usize, arguments: String) -> Self {
+        Self::ToolCallArgument { index, arguments }
     }
 
     /// Create a new end chunk with a finish reason
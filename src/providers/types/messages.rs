@@ -1,21 +1,27 @@
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::fmt;
 use thiserror::Error;
 
 use crate::tools::ToolCall;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "role", rename_all = "snake_case")]
 pub enum Message {
     User {
         content: String,
     },
     Assistant {
         content: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
         tool_calls: Option<Vec<ToolCall>>,
     },
     ToolResult {
         content: Value,
         tool_call_id: String,
+        /// Whether the tool failed and `content` describes the error
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        is_error: bool,
     },
 }
 
@@ -37,12 +43,22 @@ impl Message {
         Self::ToolResult {
             content,
             tool_call_id: tool_call_id.into(),
+            is_error: false,
+        }
+    }
+
+    /// Creates the result of a failed tool call, so the model can react to the error
+    pub fn tool_error(error: impl fmt::Display, tool_call_id: impl Into<String>) -> Self {
+        Self::ToolResult {
+            content: Value::String(error.to_string()),
+            tool_call_id: tool_call_id.into(),
+            is_error: true,
         }
     }
 
     pub fn content(&self) -> String {
         match self {
-            Self::User { content } | Self::Assistant { content, .. } => content.to_string(),
+            Self::User { content } | Self::Assistant { content, .. } => content.clone(),
             Self::ToolResult { content, .. } => content.to_string(),
         }
     }
@@ -1,5 +1,7 @@
 pub mod message_chunk;
 pub mod messages;
+pub mod usage;
 
 pub use message_chunk::{FinishReason, MessageChunk};
 pub use messages::Message;
+pub use usage::Usage;
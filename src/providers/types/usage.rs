@@ -0,0 +1,35 @@
+use std::ops::AddAssign;
+
+use serde::{Deserialize, Serialize};
+
+/// Token usage of one or more requests.
+///
+/// Input tokens read from or written to the prompt cache are counted
+/// separately and not included in `input_tokens`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Usage {
+    /// Uncached input tokens
+    pub input_tokens: u64,
+    /// Generated output tokens
+    pub output_tokens: u64,
+    /// Input tokens read from the prompt cache
+    pub cache_read_tokens: u64,
+    /// Input tokens written to the prompt cache
+    pub cache_creation_tokens: u64,
+}
+
+impl Usage {
+    /// Returns whether no tokens were used
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl AddAssign for Usage {
+    fn add_assign(&mut self, other: Self) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.cache_read_tokens += other.cache_read_tokens;
+        self.cache_creation_tokens += other.cache_creation_tokens;
+    }
+}
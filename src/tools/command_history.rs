@@ -1,8 +1,13 @@
-use std::fs::File;
-use std::io::{self, BufRead, BufReader};
-use std::path::PathBuf;
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use async_trait::async_trait;
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
 use serde_json::{json, Value};
 use thiserror::Error;
 
@@ -13,6 +18,8 @@ use crate::tools::types::{Tool, ToolDefinition};
 const MAX_COMMAND_LIMIT: usize = 100;
 /// The default number of commands to return if not specified
 const DEFAULT_COMMAND_LIMIT: usize = 10;
+/// Byte zsh prefixes special bytes with in its history file
+const ZSH_META: u8 = 0x83;
 
 /// Type alias for a Result with `HistoryError`
 type HistoryResult<T> = Result<T, HistoryError>;
@@ -33,67 +40,248 @@ pub enum HistoryError {
     ParseError(String),
 }
 
+/// A command from the shell history
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct HistoryEntry {
+    command: String,
+    /// Start time in seconds since the Unix epoch, if the history records it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timestamp: Option<u64>,
+}
+
+impl HistoryEntry {
+    /// Appends an entry unless its command is blank
+    fn push(entries: &mut Vec<Self>, command: &str, timestamp: Option<u64>) {
+        let command = command.trim();
+        if !command.is_empty() {
+            entries.push(Self {
+                command: command.to_string(),
+                timestamp,
+            });
+        }
+    }
+}
+
 /// Trait for parsing shell-specific history formats
 ///
 /// Implementations of this trait can parse different shell history file formats
-/// and extract the actual commands from each line.
+/// and extract the commands, which may span several lines, in the order they
+/// were run.
 trait HistoryParser: Send + Sync {
-    /// Attempts to parse a single line from a shell history file
+    /// Parses the content of a history file
     ///
     /// # Arguments
     ///
-    /// * `line` - A line from the shell history file
+    /// * `data` - The raw bytes of the history file
     ///
     /// # Returns
     ///
-    /// * `Some(String)` - The parsed command if successful
-    /// * `None` - If the line is empty or invalid
-    fn parse_line(&self, line: &str) -> Option<String>;
+    /// The commands in the file, oldest first, without blank entries
+    fn parse(&self, data: &[u8]) -> Vec<HistoryEntry>;
 }
 
 /// Parser for Zsh shell history format
 ///
-/// Handles the extended history format used by Zsh:
-/// `: timestamp:elapsed;command`
+/// Handles both the plain format and the extended history format
+/// `: timestamp:elapsed;command`. Newlines within a command are escaped with a
+/// backslash, and bytes above 0x83 are "metafied": written as 0x83 followed
+/// by the byte XOR 0x20.
 #[derive(Debug, Default)]
 struct ZshParser;
 
-impl HistoryParser for ZshParser {
-    fn parse_line(&self, line: &str) -> Option<String> {
-        let trimmed = line.trim();
-        if trimmed.is_empty() {
-            return None;
+/// Reverts the metafication of special bytes in zsh history files
+fn unmetafy(data: &[u8]) -> Vec<u8> {
+    let mut decoded = Vec::with_capacity(data.len());
+    let mut bytes = data.iter();
+    while let Some(&byte) = bytes.next() {
+        if byte == ZSH_META {
+            if let Some(&next) = bytes.next() {
+                decoded.push(next ^ 0x20);
+            }
+        } else {
+            decoded.push(byte);
         }
+    }
+    decoded
+}
 
-        if trimmed.starts_with(": ") {
-            trimmed.split_once(';').and_then(|(_, cmd)| {
-                let cmd = cmd.trim();
-                (!cmd.is_empty()).then_some(cmd.to_string())
-            })
-        } else {
-            Some(trimmed.to_string())
+/// Splits the `: timestamp:elapsed;` prefix of extended zsh history entries
+fn split_zsh_extended(entry: &str) -> Option<(u64, &str)> {
+    let rest = entry.strip_prefix(": ")?;
+    let (meta, command) = rest.split_once(';').unwrap_or((rest, ""));
+    let (start, elapsed) = meta.split_once(':')?;
+    elapsed.parse::<u64>().ok()?;
+    Some((start.trim().parse().ok()?, command))
+}
+
+impl HistoryParser for ZshParser {
+    fn parse(&self, data: &[u8]) -> Vec<HistoryEntry> {
+        let text = String::from_utf8_lossy(&unmetafy(data)).into_owned();
+        let mut entries = Vec::new();
+        let mut lines = text.lines();
+
+        while let Some(line) = lines.next() {
+            let mut entry = line.to_string();
+            while entry.ends_with('\\') {
+                let Some(next) = lines.next() else {
+                    break;
+                };
+                entry.pop();
+                entry.push('\n');
+                entry.push_str(next);
+            }
+
+            match split_zsh_extended(&entry) {
+                Some((timestamp, command)) => {
+                    HistoryEntry::push(&mut entries, command, Some(timestamp));
+                }
+                None => HistoryEntry::push(&mut entries, &entry, None),
+            }
         }
+        entries
     }
 }
 
 /// Parser for Bash shell history format
 ///
 /// Handles the simple line-based format used by Bash where each line
-/// contains just the command.
+/// contains just the command. When `HISTTIMEFORMAT` is set in the shell,
+/// exported or not, Bash writes a `#timestamp` line before every command, and
+/// the lines up to the next timestamp form one command. The timestamps are
+/// read from the file wherever they appear, since the variable is rarely
+/// visible to child processes.
 #[derive(Debug, Default)]
 struct BashParser;
 
+/// Parses a `#timestamp` line of the Bash history
+fn bash_timestamp(line: &str) -> Option<u64> {
+    let digits = line.strip_prefix('#')?;
+    if digits.is_empty() || !digits.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse().ok()
+}
+
 impl HistoryParser for BashParser {
-    fn parse_line(&self, line: &str) -> Option<String> {
-        let trimmed = line.trim();
-        (!trimmed.is_empty()).then_some(trimmed.to_string())
+    fn parse(&self, data: &[u8]) -> Vec<HistoryEntry> {
+        let text = String::from_utf8_lossy(data);
+        let mut entries = Vec::new();
+        // Command following the last timestamp, with its timestamp
+        let mut current: Option<(String, u64)> = None;
+
+        for line in text.lines() {
+            if let Some(timestamp) = bash_timestamp(line) {
+                if let Some((command, timestamp)) = current.take() {
+                    HistoryEntry::push(&mut entries, &command, Some(timestamp));
+                }
+                current = Some((String::new(), timestamp));
+            } else if let Some((command, _)) = current.as_mut() {
+                if !command.is_empty() {
+                    command.push('\n');
+                }
+                command.push_str(line);
+            } else {
+                HistoryEntry::push(&mut entries, line, None);
+            }
+        }
+        if let Some((command, timestamp)) = current {
+            HistoryEntry::push(&mut entries, &command, Some(timestamp));
+        }
+        entries
+    }
+}
+
+/// Parser for Fish shell history format
+///
+/// Handles the YAML-like format of Fish, where every command is a
+/// `- cmd: command` line followed by indented fields such as `when: timestamp`.
+/// Backslashes and newlines within commands are escaped as `\\` and `\n`.
+#[derive(Debug, Default)]
+struct FishParser;
+
+/// Reverts the escaping of backslashes and newlines in Fish history files
+fn fish_unescape(command: &str) -> String {
+    let mut unescaped = String::with_capacity(command.len());
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('\\')) => {
+                chars.next();
+                unescaped.push('\\');
+            }
+            ('\\', Some('n')) => {
+                chars.next();
+                unescaped.push('\n');
+            }
+            _ => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+impl HistoryParser for FishParser {
+    fn parse(&self, data: &[u8]) -> Vec<HistoryEntry> {
+        let text = String::from_utf8_lossy(data);
+        let mut entries = Vec::new();
+        let mut current: Option<(String, Option<u64>)> = None;
+
+        for line in text.lines() {
+            if let Some(command) = line.strip_prefix("- cmd:") {
+                if let Some((command, timestamp)) = current.take() {
+                    HistoryEntry::push(&mut entries, &command, timestamp);
+                }
+                current = Some((fish_unescape(command), None));
+            } else if let Some(when) = line.trim_start().strip_prefix("when:") {
+                if let Some((_, timestamp)) = current.as_mut() {
+                    *timestamp = when.trim().parse().ok();
+                }
+            }
+        }
+        if let Some((command, timestamp)) = current {
+            HistoryEntry::push(&mut entries, &command, timestamp);
+        }
+        entries
+    }
+}
+
+/// Returns the parser for the history of a shell, by the shell's name
+fn parser_for(shell: &str) -> Box<dyn HistoryParser> {
+    match shell {
+        "zsh" => Box::new(ZshParser),
+        "fish" => Box::new(FishParser),
+        _ => Box::new(BashParser),
+    }
+}
+
+/// Guesses the shell writing a history file from its name and first line
+fn sniff_shell(path: &Path) -> HistoryResult<&'static str> {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    if name.contains("zsh") {
+        return Ok("zsh");
+    }
+    if name.contains("fish") {
+        return Ok("fish");
+    }
+
+    let data = fs::read(path)?;
+    let first_line = data.split(|&byte| byte == b'\n').next().unwrap_or_default();
+    let first_line = String::from_utf8_lossy(first_line);
+    if split_zsh_extended(&first_line).is_some() {
+        Ok("zsh")
+    } else if first_line.starts_with("- cmd:") {
+        Ok("fish")
+    } else {
+        Ok("bash")
     }
 }
 
 /// Represents a shell history file with its associated parser
 ///
 /// This struct handles reading and parsing shell history files from different
-/// shell implementations (Zsh, Bash, etc.).
+/// shell implementations (Zsh, Bash, Fish).
 struct HistoryFile {
     path: PathBuf,
     parser: Box<dyn HistoryParser>,
@@ -112,64 +300,166 @@ impl HistoryFile {
 
     /// Attempts to detect and create a `HistoryFile` for the current user's shell
     ///
-    /// Checks for common shell history files in the user's home directory
-    /// and returns an appropriate `HistoryFile` instance.
+    /// Uses `$HISTFILE` if it is set, and otherwise the first existing history
+    /// file of Zsh, Bash and Fish, starting with the shell named by `$SHELL`.
+    /// Shells rarely export `HISTFILE`, so a history file in a custom location
+    /// is only found if the user exports it.
     ///
     /// # Returns
     ///
     /// * `Ok(HistoryFile)` - If a supported history file is found
     /// * `Err(HistoryError)` - If no history file is found or there's an error
     fn detect() -> HistoryResult<Self> {
-        let home = std::env::var("HOME").map_err(|_| HistoryError::NotFound)?;
-        let home_path = PathBuf::from(home);
+        Self::detect_with(|name| env::var(name).ok())
+    }
 
-        // Try Zsh history first (more common on modern systems)
-        let zsh_history = home_path.join(".zsh_history");
-        if zsh_history.exists() {
-            return Ok(Self::new(zsh_history, Box::new(ZshParser)));
+    /// Detects the history file using the given environment variables
+    fn detect_with(var: impl Fn(&str) -> Option<String>) -> HistoryResult<Self> {
+        if let Some(path) = var("HISTFILE")
+            .map(PathBuf::from)
+            .filter(|path| path.is_file())
+        {
+            let shell = sniff_shell(&path)?;
+            return Ok(Self::new(path, parser_for(shell)));
         }
 
-        // Fall back to Bash history
-        let bash_history = home_path.join(".bash_history");
-        if bash_history.exists() {
-            return Ok(Self::new(bash_history, Box::new(BashParser)));
+        let home = PathBuf::from(var("HOME").ok_or(HistoryError::NotFound)?);
+        let zsh_dir = var("ZDOTDIR").map_or_else(|| home.clone(), PathBuf::from);
+        let data_dir =
+            var("XDG_DATA_HOME").map_or_else(|| home.join(".local/share"), PathBuf::from);
+        let mut candidates = [
+            ("zsh", zsh_dir.join(".zsh_history")),
+            ("bash", home.join(".bash_history")),
+            ("fish", data_dir.join("fish/fish_history")),
+        ];
+
+        // The history of the login shell comes first
+        if let Some(shell) = var("SHELL") {
+            let login_shell = Path::new(&shell)
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned());
+            candidates.sort_by_key(|(name, _)| login_shell.as_deref() != Some(*name));
         }
 
-        Err(HistoryError::NotFound)
+        candidates
+            .into_iter()
+            .find(|(_, path)| path.is_file())
+            .map(|(shell, path)| Self::new(path, parser_for(shell)))
+            .ok_or(HistoryError::NotFound)
     }
 
-    /// Reads the most recent commands from the history file
+    /// Reads all commands from the history file
     ///
-    /// # Arguments
+    /// # Returns
     ///
-    /// * `limit` - Maximum number of commands to return
+    /// * `Ok(Vec<HistoryEntry>)` - The commands, oldest first
+    /// * `Err(HistoryError)` - If there's an error reading the file
+    fn read_entries(&self) -> HistoryResult<Vec<HistoryEntry>> {
+        let data = fs::read(&self.path).map_err(HistoryError::ReadError)?;
+        Ok(self.parser.parse(&data))
+    }
+}
+
+/// Selection of commands requested from the history
+#[derive(Debug)]
+struct HistoryQuery {
+    limit: usize,
+    /// Commands have to match this pattern
+    pattern: Option<Regex>,
+    /// Commands have to be run at or after this time, in seconds since the Unix epoch
+    since: Option<u64>,
+    /// Only the most recent run of every command is returned
+    unique: bool,
+}
+
+impl HistoryQuery {
+    /// Parses the arguments of a `command_history` call
     ///
-    /// # Returns
+    /// # Arguments
     ///
-    /// * `Ok(Vec<String>)` - List of recent commands
-    /// * `Err(HistoryError)` - If there's an error reading or parsing the file
-    fn read_recent_commands(&self, limit: usize) -> HistoryResult<Vec<String>> {
-        let file = File::open(&self.path).map_err(HistoryError::ReadError)?;
-        let reader = BufReader::new(file);
-
-        let mut commands: Vec<String> = reader
-            .lines()
-            .filter_map(Result::ok)
-            .filter_map(|line| self.parser.parse_line(&line))
-            .collect();
-
-        // Reverse to get most recent first and remove the current command
-        commands.reverse();
-        commands = commands.into_iter().skip(1).take(limit).collect();
-
-        Ok(commands)
+    /// * `arguments` - The arguments of the call
+    /// * `now` - The current time in seconds since the Unix epoch
+    fn parse(arguments: &Value, now: u64) -> Result<Self, ToolError> {
+        #[allow(clippy::cast_possible_truncation)]
+        let limit = arguments["limit"]
+            .as_u64()
+            .unwrap_or(DEFAULT_COMMAND_LIMIT as u64)
+            .clamp(1, MAX_COMMAND_LIMIT as u64) as usize;
+
+        let pattern = match arguments["query"].as_str() {
+            Some(query) if !query.is_empty() => {
+                let pattern = if arguments["regex"].as_bool().unwrap_or(false) {
+                    query.to_string()
+                } else {
+                    regex::escape(query)
+                };
+                Some(
+                    RegexBuilder::new(&pattern)
+                        .build()
+                        .map_err(|e| ToolError::InvalidArgument(format!("Invalid regex: {e}")))?,
+                )
+            }
+            _ => None,
+        };
+
+        let since = match &arguments["since_minutes"] {
+            Value::Null => None,
+            minutes => Some(
+                now.saturating_sub(
+                    minutes
+                        .as_u64()
+                        .ok_or_else(|| {
+                            ToolError::InvalidArgument(
+                                "since_minutes must be a positive integer".to_string(),
+                            )
+                        })?
+                        .saturating_mul(60),
+                ),
+            ),
+        };
+
+        Ok(Self {
+            limit,
+            pattern,
+            since,
+            unique: arguments["unique"].as_bool().unwrap_or(false),
+        })
+    }
+
+    /// Selects the requested commands, most recent first. The most recent
+    /// command of the history is skipped, as it started this program.
+    fn apply(&self, entries: Vec<HistoryEntry>) -> Vec<HistoryEntry> {
+        let mut seen = HashSet::new();
+        entries
+            .into_iter()
+            .rev()
+            .skip(1)
+            .filter(|entry| {
+                self.pattern
+                    .as_ref()
+                    .is_none_or(|pattern| pattern.is_match(&entry.command))
+            })
+            .filter(|entry| {
+                self.since
+                    .is_none_or(|since| entry.timestamp.is_some_and(|time| time >= since))
+            })
+            .filter(|entry| !self.unique || seen.insert(entry.command.clone()))
+            .take(self.limit)
+            .collect()
     }
 }
 
+/// Returns the current time as seconds since the Unix epoch
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
 /// Tool for retrieving recent shell command history
 ///
 /// This tool provides access to the user's shell command history,
-/// supporting both Zsh and Bash history formats.
+/// supporting Zsh, Bash and Fish history formats.
 #[derive(Debug, Default)]
 pub struct CommandHistoryTool;
 
@@ -178,7 +468,11 @@ impl Tool for CommandHistoryTool {
     fn definition(&self) -> ToolDefinition {
         ToolDefinition {
             name: "command_history".to_string(),
-            description: "Retrieves the user's recently executed terminal commands.".to_string(),
+            description: "Retrieves the user's recently executed terminal commands, most \
+                          recent first, with the time they were run in seconds since the Unix \
+                          epoch if the shell records it (Bash only does with HISTTIMEFORMAT \
+                          set)."
+                .to_string(),
             parameters: json!({
                 "type": "object",
                 "properties": {
@@ -187,29 +481,45 @@ impl Tool for CommandHistoryTool {
                         "description": format!("Number of recent commands to retrieve from history (default: {}, max: {})", DEFAULT_COMMAND_LIMIT, MAX_COMMAND_LIMIT),
                         "minimum": 1,
                         "maximum": MAX_COMMAND_LIMIT
+                    },
+                    "query": {
+                        "type": "string",
+                        "description": "Only return commands containing this text"
+                    },
+                    "regex": {
+                        "type": "boolean",
+                        "description": "Treat query as a regular expression (default: false)"
+                    },
+                    "since_minutes": {
+                        "type": "integer",
+                        "description": "Only return commands run within this many minutes. Commands without a recorded time are left out",
+                        "minimum": 1
+                    },
+                    "unique": {
+                        "type": "boolean",
+                        "description": "Only return the most recent run of every command (default: false)"
                     }
                 }
             }),
         }
     }
 
+    fn is_read_only(&self) -> bool {
+        true
+    }
+
     async fn execute(&self, arguments: &Value) -> Result<Value, ToolError> {
-        #[allow(clippy::cast_possible_truncation)]
-        let limit = arguments["limit"]
-            .as_u64()
-            .unwrap_or(DEFAULT_COMMAND_LIMIT as u64)
-            .min(MAX_COMMAND_LIMIT as u64) as usize;
+        let query = HistoryQuery::parse(arguments, now())?;
 
         let history_file = HistoryFile::detect().map_err(|e| {
             ToolError::ExecutionError(format!("Failed to locate history file: {e}"))
         })?;
 
-        let commands = history_file
-            .read_recent_commands(limit)
+        let entries = history_file
+            .read_entries()
             .map_err(|e| ToolError::ExecutionError(format!("Failed to read history: {e}")))?;
 
-        let result = format!("[{}]", commands.join(","));
-        Ok(json!(result))
+        Ok(json!(query.apply(entries)))
     }
 }
 
@@ -217,7 +527,7 @@ impl Tool for CommandHistoryTool {
 mod tests {
     use super::*;
     use std::io::Write;
-    use tempfile::NamedTempFile;
+    use tempfile::{NamedTempFile, TempDir};
     use tokio;
 
     /// Helper function to create a temporary history file with given content
@@ -229,52 +539,87 @@ mod tests {
         file
     }
 
+    fn entry(command: &str, timestamp: Option<u64>) -> HistoryEntry {
+        HistoryEntry {
+            command: command.to_string(),
+            timestamp,
+        }
+    }
+
+    fn commands(entries: &[HistoryEntry]) -> Vec<&str> {
+        entries.iter().map(|entry| entry.command.as_str()).collect()
+    }
+
     mod parser_tests {
         use super::*;
 
         #[test]
         fn test_zsh_parser() {
-            let parser = ZshParser;
-
-            // Test cases
-            let cases = [
-                (": 1707394841:0;ls -la", Some("ls -la")),
-                ("", None),
-                (": 1707394841:0", None),
-                (": 1707394841:0;  ", None),
-                (": 1707394841:0;echo 'hello'", Some("echo 'hello'")),
-                (": 1707394841:0;echo 'world'", Some("echo 'world'")),
-            ];
+            let history = ": 1707394841:0;ls -la\n\n: 1707394842:0\n: 1707394843:0;  \n: 1707394844:3;echo 'hello'\nplain command\n";
+
+            assert_eq!(
+                ZshParser.parse(history.as_bytes()),
+                [
+                    entry("ls -la", Some(1_707_394_841)),
+                    entry("echo 'hello'", Some(1_707_394_844)),
+                    entry("plain command", None),
+                ]
+            );
+        }
 
-            for (input, expected) in cases {
-                assert_eq!(
-                    parser.parse_line(input),
-                    expected.map(String::from),
-                    "Failed on input: {input}"
-                );
-            }
+        #[test]
+        fn test_zsh_parser_multiline_and_metafied() {
+            let mut history =
+                b": 1707394841:0;for f in *; do\\\n  echo \"$f\"\\\ndone\n: 1707394842:0;echo "
+                    .to_vec();
+            // "ä" is 0xC3 0xA4; 0xA4 is metafied as 0x83 0x84
+            history.extend_from_slice(&[0xC3, ZSH_META, 0x84, b'\n']);
+
+            assert_eq!(
+                ZshParser.parse(&history),
+                [
+                    entry("for f in *; do\n  echo \"$f\"\ndone", Some(1_707_394_841)),
+                    entry("echo ä", Some(1_707_394_842)),
+                ]
+            );
         }
 
         #[test]
         fn test_bash_parser() {
-            let parser = BashParser;
-
-            // Test cases
-            let cases = [
-                ("ls -la", Some("ls -la")),
-                ("  cd /home  ", Some("cd /home")),
-                ("", None),
-                ("  ", None),
-                ("echo 'hello'", Some("echo 'hello'")),
-            ];
+            let history = "ls -la\n  cd /home  \n\n  \necho 'hello'\n# a comment\n";
 
-            for (input, expected) in cases {
-                assert_eq!(
-                    parser.parse_line(input),
-                    expected.map(String::from),
-                    "Failed on input: {input}"
-                );
-            }
+            assert_eq!(
+                commands(&BashParser.parse(history.as_bytes())),
+                ["ls -la", "cd /home", "echo 'hello'", "# a comment"]
+            );
+        }
+
+        #[test]
+        fn test_bash_parser_timestamps() {
+            let history = "old command\n#1707394841\nls -la\n#1707394842\nfor f in *; do\n  echo \"$f\"\ndone\n";
+
+            assert_eq!(
+                BashParser.parse(history.as_bytes()),
+                [
+                    entry("old command", None),
+                    entry("ls -la", Some(1_707_394_841)),
+                    entry("for f in *; do\n  echo \"$f\"\ndone", Some(1_707_394_842)),
+                ]
+            );
+        }
+
+        #[test]
+        fn test_fish_parser() {
+            let history = "- cmd: cd src\n  when: 1707394841\n  paths:\n    - src\n- cmd: echo a\\\\b\\nc\n  when: 1707394842\n- cmd: ls\n";
+
+            assert_eq!(
+                FishParser.parse(history.as_bytes()),
+                [
+                    entry("cd src", Some(1_707_394_841)),
+                    entry("echo a\\b\nc", Some(1_707_394_842)),
+                    entry("ls", None),
+                ]
+            );
         }
     }
 
@@ -291,8 +636,9 @@ mod tests {
             let temp_file = create_temp_history(&content);
             let history = HistoryFile::new(temp_file.path().to_path_buf(), Box::new(ZshParser));
 
-            let commands = history.read_recent_commands(2).unwrap();
-            assert_eq!(commands, vec!["cd /home", "ls -la"]);
+            let query = HistoryQuery::parse(&json!({ "limit": 2 }), 0).unwrap();
+            let entries = query.apply(history.read_entries().unwrap());
+            assert_eq!(commands(&entries), ["cd /home", "ls -la"]);
         }
 
         #[test]
@@ -301,8 +647,9 @@ mod tests {
             let temp_file = create_temp_history(&content);
             let history = HistoryFile::new(temp_file.path().to_path_buf(), Box::new(BashParser));
 
-            let commands = history.read_recent_commands(2).unwrap();
-            assert_eq!(commands, vec!["cd /home", "ls -la"]);
+            let query = HistoryQuery::parse(&json!({ "limit": 2 }), 0).unwrap();
+            let entries = query.apply(history.read_entries().unwrap());
+            assert_eq!(commands(&entries), ["cd /home", "ls -la"]);
         }
 
         #[test]
@@ -310,8 +657,8 @@ mod tests {
             let temp_file = create_temp_history(&[]);
             let history = HistoryFile::new(temp_file.path().to_path_buf(), Box::new(BashParser));
 
-            let commands = history.read_recent_commands(10).unwrap();
-            assert!(commands.is_empty());
+            let query = HistoryQuery::parse(&json!({}), 0).unwrap();
+            assert!(query.apply(history.read_entries().unwrap()).is_empty());
         }
 
         #[test]
@@ -321,10 +668,127 @@ mod tests {
                 create_temp_history(&content.iter().map(AsRef::as_ref).collect::<Vec<_>>());
             let history = HistoryFile::new(temp_file.path().to_path_buf(), Box::new(BashParser));
 
-            let commands = history.read_recent_commands(5).unwrap();
-            assert_eq!(commands.len(), 5);
-            assert_eq!(commands[0], "command 18");
-            assert_eq!(commands[4], "command 14");
+            let query = HistoryQuery::parse(&json!({ "limit": 5 }), 0).unwrap();
+            let entries = query.apply(history.read_entries().unwrap());
+            assert_eq!(entries.len(), 5);
+            assert_eq!(entries[0].command, "command 18");
+            assert_eq!(entries[4].command, "command 14");
+        }
+
+        #[test]
+        fn test_detect_prefers_histfile_and_login_shell() {
+            let home = TempDir::new().unwrap();
+            std::fs::write(home.path().join(".zsh_history"), ": 1:0;ls\n").unwrap();
+            std::fs::write(home.path().join(".bash_history"), "ls\n").unwrap();
+            let custom = home.path().join("history");
+            std::fs::write(&custom, "- cmd: ls\n  when: 1\n").unwrap();
+            let env = |vars: &'static [(&'static str, &'static str)]| {
+                let home = home.path().to_string_lossy().into_owned();
+                let custom = custom.to_string_lossy().into_owned();
+                move |name: &str| match name {
+                    "HOME" => Some(home.clone()),
+                    "HISTFILE" if vars.iter().any(|(var, _)| *var == "HISTFILE") => {
+                        Some(custom.clone())
+                    }
+                    _ => vars
+                        .iter()
+                        .find(|(var, _)| *var == name)
+                        .map(|(_, value)| (*value).to_string()),
+                }
+            };
+
+            let default = HistoryFile::detect_with(env(&[])).unwrap();
+            let bash = HistoryFile::detect_with(env(&[("SHELL", "/bin/bash")])).unwrap();
+            let histfile = HistoryFile::detect_with(env(&[("HISTFILE", "")])).unwrap();
+
+            assert_eq!(default.path, home.path().join(".zsh_history"));
+            assert_eq!(bash.path, home.path().join(".bash_history"));
+            assert_eq!(histfile.path, custom);
+            assert_eq!(histfile.read_entries().unwrap(), [entry("ls", Some(1))]);
+        }
+
+        #[test]
+        fn test_detected_bash_history_has_timestamps() {
+            let home = TempDir::new().unwrap();
+            std::fs::write(
+                home.path().join(".bash_history"),
+                "#1707394841
+ls
+",
+            )
+            .unwrap();
+            let home_dir = home.path().to_string_lossy().into_owned();
+
+            // Neither HISTFILE nor HISTTIMEFORMAT is exported
+            let history = HistoryFile::detect_with(|name| match name {
+                "HOME" => Some(home_dir.clone()),
+                "SHELL" => Some("/bin/bash".to_string()),
+                _ => None,
+            })
+            .unwrap();
+
+            assert_eq!(
+                history.read_entries().unwrap(),
+                [entry("ls", Some(1_707_394_841))]
+            );
+        }
+    }
+
+    mod query_tests {
+        use super::*;
+
+        fn history() -> Vec<HistoryEntry> {
+            vec![
+                entry("git status", Some(1000)),
+                entry("cargo test", Some(2000)),
+                entry("git status", Some(3000)),
+                entry("git log -p", Some(4000)),
+                entry("llm-cli 'what failed?'", Some(5000)),
+            ]
+        }
+
+        #[test]
+        fn test_filter_by_substring_and_regex() {
+            let substring = HistoryQuery::parse(&json!({ "query": "git s" }), 0).unwrap();
+            let regex =
+                HistoryQuery::parse(&json!({ "query": "^(cargo|git log)", "regex": true }), 0)
+                    .unwrap();
+
+            assert_eq!(
+                commands(&substring.apply(history())),
+                ["git status", "git status"]
+            );
+            assert_eq!(
+                commands(&regex.apply(history())),
+                ["git log -p", "cargo test"]
+            );
+            assert!(HistoryQuery::parse(&json!({ "query": "(", "regex": true }), 0).is_err());
+        }
+
+        #[test]
+        fn test_time_window_and_deduplication() {
+            let recent = HistoryQuery::parse(&json!({ "since_minutes": 1 }), 3050).unwrap();
+            let unique = HistoryQuery::parse(&json!({ "unique": true }), 0).unwrap();
+
+            assert_eq!(
+                recent.apply(history()),
+                [
+                    entry("git log -p", Some(4000)),
+                    entry("git status", Some(3000))
+                ]
+            );
+            assert_eq!(
+                commands(&unique.apply(history())),
+                ["git log -p", "git status", "cargo test"]
+            );
+        }
+
+        #[test]
+        fn test_entries_serialize_with_timestamps() {
+            assert_eq!(
+                json!([entry("ls", Some(1)), entry("pwd", None)]),
+                json!([{ "command": "ls", "timestamp": 1 }, { "command": "pwd" }])
+            );
         }
     }
 
@@ -337,8 +801,7 @@ mod tests {
             let args = json!({ "limit": 5 });
 
             if let Ok(value) = tool.execute(&args).await {
-                let num_commands = value.to_string().split(',').count();
-                assert_eq!(num_commands, 5);
+                assert!(value.as_array().unwrap().len() <= 5);
             }
         }
 
@@ -348,8 +811,7 @@ mod tests {
             let args = json!({});
 
             if let Ok(value) = tool.execute(&args).await {
-                let num_commands = value.to_string().split(',').count();
-                assert_eq!(num_commands, DEFAULT_COMMAND_LIMIT);
+                assert!(value.as_array().unwrap().len() <= DEFAULT_COMMAND_LIMIT);
             }
         }
 
@@ -359,8 +821,7 @@ mod tests {
             let args = json!({ "limit": 200 }); // Exceeds maximum
 
             if let Ok(value) = tool.execute(&args).await {
-                let num_commands = value.to_string().split(',').count();
-                assert_eq!(num_commands, MAX_COMMAND_LIMIT);
+                assert!(value.as_array().unwrap().len() <= MAX_COMMAND_LIMIT);
             }
         }
     }
@@ -0,0 +1,224 @@
+use async_trait::async_trait;
+use serde_json::{json, Value};
+
+use crate::core::{error::ToolError, CustomToolConfig, LLMError, ToolsConfig};
+use crate::tools::execute_command::{configured_shell, process_limits};
+use crate::tools::permissions::PermissionPolicy;
+use crate::tools::process::{self, ProcessLimits};
+use crate::tools::types::{Tool, ToolDefinition, MAX_NAME_LENGTH};
+
+/// Prefix of the environment variables holding the arguments
+const ARGUMENT_ENV_PREFIX: &str = "TOOL_ARG_";
+
+/// Tool defined in the configuration, running a command with the arguments
+/// of the call and returning its stdout
+pub struct CustomTool {
+    definition: ToolDefinition,
+    command: String,
+    read_only: bool,
+    shell: String,
+    limits: ProcessLimits,
+    policy: PermissionPolicy,
+}
+
+impl CustomTool {
+    /// Creates the tool from its configuration, running the command with the
+    /// shell and limits of the other tools
+    ///
+    /// # Errors
+    /// Returns a configuration error if the name is not a valid tool name
+    pub fn new(
+        config: &CustomToolConfig,
+        tools: &ToolsConfig,
+        policy: PermissionPolicy,
+    ) -> Result<Self, LLMError> {
+        let valid_name = !config.name.is_empty()
+            && config.name.len() <= MAX_NAME_LENGTH
+            && config
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if !valid_name {
+            return Err(LLMError::ConfigError(format!(
+                "Invalid custom tool name '{}', use up to {MAX_NAME_LENGTH} letters, digits, '_' or '-'",
+                config.name
+            )));
+        }
+
+        Ok(Self {
+            definition: ToolDefinition {
+                name: config.name.clone(),
+                description: config.description.clone(),
+                parameters: config
+                    .parameters
+                    .clone()
+                    .unwrap_or_else(|| json!({ "type": "object", "properties": {} })),
+            },
+            command: config.command.clone(),
+            read_only: config.read_only,
+            shell: configured_shell(tools),
+            limits: process_limits(tools),
+            policy,
+        })
+    }
+}
+
+/// Returns the environment variables passing the arguments to the command,
+/// e.g. `TOOL_ARG_ISSUE_KEY` for `issue_key`. Strings are passed as they are,
+/// other values as JSON.
+fn argument_env(arguments: &Value) -> Vec<(String, String)> {
+    let Some(arguments) = arguments.as_object() else {
+        return Vec::new();
+    };
+    arguments
+        .iter()
+        .filter(|(_, value)| !value.is_null())
+        .map(|(name, value)| {
+            let name = name
+                .chars()
+                .map(|c| {
+                    if c.is_ascii_alphanumeric() {
+                        c.to_ascii_uppercase()
+                    } else {
+                        '_'
+                    }
+                })
+                .collect::<String>();
+            let value = value
+                .as_str()
+                .map_or_else(|| value.to_string(), ToString::to_string);
+            (format!("{ARGUMENT_ENV_PREFIX}{name}"), value)
+        })
+        .collect()
+}
+
+#[async_trait]
+impl Tool for CustomTool {
+    fn definition(&self) -> ToolDefinition {
+        self.definition.clone()
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    async fn execute(&self, arguments: &Value) -> Result<Value, ToolError> {
+        let name = &self.definition.name;
+        if !self.read_only {
+            self.policy.approve_tool(name, arguments)?;
+        }
+
+        let mut shell = tokio::process::Command::new(&self.shell);
+        shell
+            .arg("-c")
+            .arg(&self.command)
+            .envs(argument_env(arguments));
+        let input = arguments.to_string();
+        let output = process::run(shell, self.limits, Some(input.as_bytes()))
+            .await
+            .map_err(|e| ToolError::ExecutionError(format!("Failed to run {}: {e}", self.shell)))?;
+
+        if output.timed_out || output.interrupted {
+            let reason = if output.timed_out {
+                "timed out"
+            } else {
+                "was interrupted"
+            };
+            return Err(ToolError::ExecutionError(format!("{name} {reason}")));
+        }
+        if output.exit_code != Some(0) {
+            let status = output
+                .exit_code
+                .map_or_else(|| "a signal".to_string(), |code| format!("code {code}"));
+            return Err(ToolError::ExecutionError(format!(
+                "{name} failed with {status}: {}",
+                output.stderr
+            )));
+        }
+        Ok(Value::String(output.stdout.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::PermissionsConfig;
+    use crate::tools::ApprovalMode;
+
+    fn tool(command: &str, read_only: bool, mode: ApprovalMode) -> CustomTool {
+        let config = CustomToolConfig {
+            name: "lookup".to_string(),
+            description: "Looks something up".to_string(),
+            parameters: None,
+            command: command.to_string(),
+            read_only,
+        };
+        let tools = ToolsConfig {
+            shell: Some("/bin/sh".to_string()),
+            ..ToolsConfig::default()
+        };
+        let policy = PermissionPolicy::new(&PermissionsConfig::default(), mode).unwrap();
+        CustomTool::new(&config, &tools, policy).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_arguments_in_env_and_stdin() {
+        let tool = tool(
+            r#"echo "$TOOL_ARG_ISSUE_KEY $TOOL_ARG_LIMIT $TOOL_ARG_TAGS"; cat"#,
+            true,
+            ApprovalMode::Never,
+        );
+
+        let result = tool
+            .execute(&json!({ "issue-key": "OPS-1; rm -rf ~", "limit": 3, "tags": ["a"] }))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            result,
+            "OPS-1; rm -rf ~ 3 [\"a\"]\n{\"issue-key\":\"OPS-1; rm -rf ~\",\"limit\":3,\"tags\":[\"a\"]}"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failing_command() {
+        let tool = tool("echo 'no such issue' >&2; exit 2", false, ApprovalMode::Yes);
+
+        let result = tool.execute(&json!({})).await;
+
+        assert!(matches!(
+            result,
+            Err(ToolError::ExecutionError(e)) if e == "lookup failed with code 2: no such issue\n"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_calls_are_approved_by_policy() {
+        let tool = tool("touch should-not-exist", false, ApprovalMode::Never);
+
+        assert!(matches!(
+            tool.execute(&json!({})).await,
+            Err(ToolError::PermissionDenied(_))
+        ));
+    }
+
+    #[test]
+    fn test_invalid_name() {
+        let config = CustomToolConfig {
+            name: "jira lookup".to_string(),
+            description: String::new(),
+            parameters: None,
+            command: "true".to_string(),
+            read_only: true,
+        };
+
+        assert!(matches!(
+            CustomTool::new(
+                &config,
+                &ToolsConfig::default(),
+                PermissionPolicy::default()
+            ),
+            Err(LLMError::ConfigError(_))
+        ));
+    }
+}
@@ -0,0 +1,200 @@
+use async_trait::async_trait;
+use serde_json::{json, Value};
+
+use crate::core::error::ToolError;
+use crate::tools::file_change::FileChange;
+use crate::tools::permissions::PermissionPolicy;
+use crate::tools::types::{Tool, ToolDefinition};
+use crate::tools::workspace::Workspace;
+
+/// Tool replacing text in an existing file, after the user reviewed the diff
+pub struct EditFileTool {
+    workspace: Workspace,
+    policy: PermissionPolicy,
+}
+
+/// Reads a required string argument
+fn string_argument<'a>(arguments: &'a Value, key: &str) -> Result<&'a str, ToolError> {
+    arguments[key]
+        .as_str()
+        .ok_or_else(|| ToolError::InvalidArgument(format!("{key} must be a string")))
+}
+
+#[async_trait]
+impl Tool for EditFileTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "edit_file".to_string(),
+            description: "Edits a file in the workspace by replacing old_string with \
+                          new_string. old_string must match the file exactly, including \
+                          whitespace, and must be unique unless replace_all is set. Include \
+                          enough surrounding lines to make it unique. The user reviews the \
+                          diff before it is written"
+                .to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Path of the file, relative to the workspace root"
+                    },
+                    "old_string": {
+                        "type": "string",
+                        "description": "The exact text to replace"
+                    },
+                    "new_string": {
+                        "type": "string",
+                        "description": "The text to replace it with"
+                    },
+                    "replace_all": {
+                        "type": "boolean",
+                        "description": "Replace every occurrence of old_string (default: false)"
+                    }
+                },
+                "required": ["path", "old_string", "new_string"]
+            }),
+        }
+    }
+
+    async fn execute(&self, arguments: &Value) -> Result<Value, ToolError> {
+        let path = string_argument(arguments, "path")?;
+        let old_string = string_argument(arguments, "old_string")?;
+        let new_string = string_argument(arguments, "new_string")?;
+        let replace_all = arguments["replace_all"].as_bool().unwrap_or(false);
+        if old_string.is_empty() {
+            return Err(ToolError::InvalidArgument(
+                "old_string must not be empty".to_string(),
+            ));
+        }
+
+        let mut change = FileChange::read(&self.workspace, path).await?;
+        let Some(old) = &change.old else {
+            return Err(ToolError::InvalidArgument(format!(
+                "{} does not exist, use write_file to create it",
+                change.display
+            )));
+        };
+
+        let replacements = match old.matches(old_string).count() {
+            0 => {
+                return Err(ToolError::InvalidArgument(format!(
+                    "old_string was not found in {}",
+                    change.display
+                )))
+            }
+            count if count > 1 && !replace_all => {
+                return Err(ToolError::InvalidArgument(format!(
+                    "old_string occurs {count} times in {}, include more context to \
+                     make it unique or set replace_all",
+                    change.display
+                )))
+            }
+            count => count,
+        };
+        change.new = old.replacen(old_string, new_string, replacements);
+
+        let stats = change.apply("edit_file", &self.policy).await?;
+        Ok(json!({
+            "path": change.display,
+            "replacements": replacements,
+            "lines_added": stats.added,
+            "lines_removed": stats.removed,
+        }))
+    }
+}
+
+impl EditFileTool {
+    /// Creates the tool editing files within the workspace, approving
+    /// changes through the policy
+    pub const fn new(workspace: Workspace, policy: PermissionPolicy) -> Self {
+        Self { workspace, policy }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::PermissionsConfig;
+    use crate::tools::ApprovalMode;
+    use tempfile::TempDir;
+
+    const SOURCE: &str = "fn add(a: i32, b: i32) -> i32 {\n    a - b\n}\n\nfn sub(a: i32, b: i32) -> i32 {\n    a - b\n}\n";
+
+    fn tool(mode: ApprovalMode) -> (TempDir, EditFileTool) {
+        let dir = TempDir::new().unwrap();
+        std::fs::write(dir.path().join("lib.rs"), SOURCE).unwrap();
+        let workspace = Workspace::new(dir.path()).unwrap();
+        let policy = PermissionPolicy::new(&PermissionsConfig::default(), mode).unwrap();
+        (dir, EditFileTool::new(workspace, policy))
+    }
+
+    fn read(dir: &TempDir) -> String {
+        std::fs::read_to_string(dir.path().join("lib.rs")).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_replaces_unique_text() {
+        let (dir, tool) = tool(ApprovalMode::Yes);
+
+        let result = tool
+            .execute(&json!({
+                "path": "lib.rs",
+                "old_string": "fn add(a: i32, b: i32) -> i32 {\n    a - b",
+                "new_string": "fn add(a: i32, b: i32) -> i32 {\n    a + b",
+            }))
+            .await
+            .unwrap();
+
+        assert_eq!(read(&dir), SOURCE.replacen("a - b", "a + b", 1));
+        assert_eq!(result["replacements"], 1);
+        assert_eq!(result["lines_added"], 1);
+        assert_eq!(result["lines_removed"], 1);
+    }
+
+    #[tokio::test]
+    async fn test_ambiguous_text_needs_replace_all() {
+        let (dir, tool) = tool(ApprovalMode::Yes);
+        let arguments = json!({ "path": "lib.rs", "old_string": "a - b", "new_string": "b - a" });
+
+        let result = tool.execute(&arguments).await;
+        assert!(matches!(result, Err(ToolError::InvalidArgument(e)) if e.contains("2 times")));
+        assert_eq!(read(&dir), SOURCE);
+
+        let mut arguments = arguments;
+        arguments["replace_all"] = json!(true);
+        let result = tool.execute(&arguments).await.unwrap();
+        assert_eq!(result["replacements"], 2);
+        assert_eq!(read(&dir), SOURCE.replace("a - b", "b - a"));
+    }
+
+    #[tokio::test]
+    async fn test_missing_text_and_file() {
+        let (_dir, tool) = tool(ApprovalMode::Yes);
+
+        for arguments in [
+            json!({ "path": "lib.rs", "old_string": "a * b", "new_string": "a" }),
+            json!({ "path": "main.rs", "old_string": "a", "new_string": "b" }),
+            json!({ "path": "lib.rs", "old_string": "", "new_string": "b" }),
+        ] {
+            assert!(
+                matches!(
+                    tool.execute(&arguments).await,
+                    Err(ToolError::InvalidArgument(_))
+                ),
+                "{arguments}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_denied_edit_leaves_file_unchanged() {
+        let (dir, tool) = tool(ApprovalMode::Never);
+
+        let result = tool
+            .execute(&json!({ "path": "lib.rs", "old_string": "fn sub", "new_string": "fn minus" }))
+            .await;
+
+        assert!(matches!(result, Err(ToolError::PermissionDenied(_))));
+        assert_eq!(read(&dir), SOURCE);
+    }
+}
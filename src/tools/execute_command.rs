@@ -1,15 +1,40 @@
-use std::io::{self, Write};
-use std::process::Command;
+use std::env;
+use std::time::Duration;
 
 use async_trait::async_trait;
 use serde_json::{json, Value};
 
-use crate::core::error::ToolError;
+use crate::core::{error::ToolError, ToolsConfig};
+use crate::tools::permissions::{Decision, PermissionPolicy};
+use crate::tools::process::{self, ProcessLimits, ProcessOutput};
 use crate::tools::types::{Tool, ToolDefinition};
 
-pub struct ExecuteCommandTool;
+/// Shell used when neither the configuration nor `$SHELL` name one
+const DEFAULT_SHELL: &str = "/bin/sh";
+
+/// Tool running shell commands, including pipes, redirections and quoting
+pub struct ExecuteCommandTool {
+    shell: String,
+    limits: ProcessLimits,
+    policy: PermissionPolicy,
+}
+
+/// Returns the configured shell, falling back to `$SHELL` and then `/bin/sh`
+pub fn configured_shell(config: &ToolsConfig) -> String {
+    config
+        .shell
+        .clone()
+        .unwrap_or_else(|| env::var("SHELL").unwrap_or_else(|_| DEFAULT_SHELL.to_string()))
+}
+
+/// Returns the configured limits for commands run by tools
+pub(super) const fn process_limits(config: &ToolsConfig) -> ProcessLimits {
+    ProcessLimits {
+        timeout: Duration::from_secs(config.timeout_secs),
+        max_output_bytes: config.max_output_bytes,
+    }
+}
 
-const CONFIRMATION_PROMPT: &str = "Do you want to execute the command: '{}' ? [y/N] ";
 const COMMAND_EMPTY_ERROR: &str = "command cannot be empty";
 const COMMAND_STRING_ERROR: &str = "command must be a string";
 
@@ -18,7 +43,10 @@ impl Tool for ExecuteCommandTool {
     fn definition(&self) -> ToolDefinition {
         ToolDefinition {
             name: "execute_command".to_string(),
-            description: "Executes a command on the command line and returns its output"
+            description: "Executes a command in the user's shell and returns its exit code, \
+                          stdout and stderr. Pipes, redirections and quoting are supported. \
+                          Long running commands are killed after a timeout and large \
+                          outputs are truncated in the middle"
                 .to_string(),
             parameters: json!({
                 "type": "object",
@@ -36,76 +64,121 @@ impl Tool for ExecuteCommandTool {
     async fn execute(&self, arguments: &Value) -> Result<Value, ToolError> {
         let command = Self::extract_command(arguments)?;
 
-        if !Self::confirm_execution(&command)? {
-            return Ok(json!("stderr: Command execution cancelled by user"));
+        if let Decision::Deny(reason) = self.policy.approve(&command)? {
+            return Err(ToolError::PermissionDenied(format!(
+                "'{command}' was denied by {reason}"
+            )));
         }
 
-        let (program, args) = Self::parse_command(&command)?;
-        let output = Self::run_command(program, args)?;
-
-        Ok(json!(Self::format_output(&output)))
+        self.run_approved(&command).await
     }
 }
 
 impl ExecuteCommandTool {
+    /// Creates the tool with the configured shell and limits, approving
+    /// commands through the policy. The shell falls back to `$SHELL` and
+    /// then `/bin/sh`
+    pub fn new(config: &ToolsConfig, policy: PermissionPolicy) -> Self {
+        Self {
+            shell: configured_shell(config),
+            limits: process_limits(config),
+            policy,
+        }
+    }
+
+    /// Runs a command that was already approved and returns the result of a
+    /// tool call, for callers that ask for approval themselves
+    pub async fn run_approved(&self, command: &str) -> Result<Value, ToolError> {
+        let output = self.run_command(command).await?;
+
+        Ok(Self::format_output(&output))
+    }
+
     /// Extracts command string from arguments
     fn extract_command(arguments: &Value) -> Result<String, ToolError> {
-        arguments["command"]
+        let command = arguments["command"]
             .as_str()
-            .map(String::from)
-            .ok_or_else(|| ToolError::InvalidArgument(String::from(COMMAND_STRING_ERROR)))
+            .ok_or_else(|| ToolError::InvalidArgument(String::from(COMMAND_STRING_ERROR)))?;
+
+        if command.trim().is_empty() {
+            return Err(ToolError::InvalidArgument(String::from(
+                COMMAND_EMPTY_ERROR,
+            )));
+        }
+        Ok(command.to_string())
     }
 
-    /// Prompts for user confirmation
-    fn confirm_execution(command: &str) -> Result<bool, ToolError> {
-        println!();
-        print!("{}", CONFIRMATION_PROMPT.replace("{}", command));
+    /// Runs the command with `<shell> -c` within the configured limits
+    async fn run_command(&self, command: &str) -> Result<ProcessOutput, ToolError> {
+        let mut shell = tokio::process::Command::new(&self.shell);
+        shell.arg("-c").arg(command);
 
-        io::stdout()
-            .flush()
-            .map_err(|e| ToolError::ExecutionError(e.to_string()))?;
+        process::run(shell, self.limits, None)
+            .await
+            .map_err(|e| ToolError::ExecutionError(format!("Failed to run {}: {e}", self.shell)))
+    }
+
+    /// Formats the exit status, output and limits hit by the command
+    fn format_output(output: &ProcessOutput) -> Value {
+        json!({
+            "exit_code": output.exit_code,
+            "signal": output.signal,
+            "stdout": output.stdout.to_string(),
+            "stderr": output.stderr.to_string(),
+            "duration_ms": u64::try_from(output.duration.as_millis()).unwrap_or(u64::MAX),
+            "timed_out": output.timed_out,
+            "interrupted": output.interrupted,
+            "stdout_truncated": output.stdout.is_truncated(),
+            "stderr_truncated": output.stderr.is_truncated(),
+        })
+    }
+}
 
-        let mut input = String::new();
-        io::stdin()
-            .read_line(&mut input)
-            .map_err(|e| ToolError::ExecutionError(e.to_string()))?;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        Ok(matches!(input.trim().to_lowercase().as_str(), "y" | "yes"))
+    async fn run(command: &str) -> Value {
+        let config = ToolsConfig {
+            shell: Some("/bin/sh".to_string()),
+            ..ToolsConfig::default()
+        };
+        let tool = ExecuteCommandTool::new(&config, PermissionPolicy::default());
+        ExecuteCommandTool::format_output(&tool.run_command(command).await.unwrap())
     }
 
-    /// Parses command string into program and arguments
-    fn parse_command(command: &str) -> Result<(&str, Vec<&str>), ToolError> {
-        let mut parts = command.split_whitespace();
-        let program = parts
-            .next()
-            .ok_or_else(|| ToolError::InvalidArgument(String::from(COMMAND_EMPTY_ERROR)))?;
-        let args: Vec<&str> = parts.collect();
+    #[tokio::test]
+    async fn test_pipes_and_quoting() {
+        let result = run("echo 'hello   world' | tr a-z A-Z && echo \"$((1 + 2))\"").await;
 
-        Ok((program, args))
+        assert_eq!(result["stdout"], "HELLO   WORLD\n3\n");
+        assert_eq!(result["exit_code"], 0);
+        assert_eq!(result["signal"], Value::Null);
     }
 
-    /// Executes the command and returns its output
-    fn run_command(program: &str, args: Vec<&str>) -> Result<std::process::Output, ToolError> {
-        Command::new(program)
-            .args(args)
-            .output()
-            .map_err(|e| ToolError::ExecutionError(e.to_string()))
+    #[tokio::test]
+    async fn test_redirection_and_exit_code() {
+        let result = run("echo oops >&2; exit 3").await;
+
+        assert_eq!(result["stdout"], "");
+        assert_eq!(result["stderr"], "oops\n");
+        assert_eq!(result["exit_code"], 3);
     }
 
-    /// Formats command output into a presentable string
-    fn format_output(output: &std::process::Output) -> String {
-        let stdout = if output.stdout.is_empty() {
-            String::new()
-        } else {
-            format!("stdout: {}", String::from_utf8_lossy(&output.stdout))
-        };
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_signal() {
+        let result = run("kill -TERM $$").await;
 
-        let stderr = if output.stderr.is_empty() {
-            String::new()
-        } else {
-            format!(", stderr: {}", String::from_utf8_lossy(&output.stderr))
-        };
+        assert_eq!(result["exit_code"], Value::Null);
+        assert_eq!(result["signal"], 15);
+    }
 
-        format!("{stdout}{stderr}")
+    #[test]
+    fn test_empty_command() {
+        assert!(matches!(
+            ExecuteCommandTool::extract_command(&json!({ "command": "  " })),
+            Err(ToolError::InvalidArgument(_))
+        ));
     }
 }
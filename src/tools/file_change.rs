@@ -0,0 +1,120 @@
+use std::io::{self, IsTerminal, Write};
+use std::path::PathBuf;
+
+use termcolor::{ColorChoice, StandardStream};
+
+use crate::core::error::ToolError;
+use crate::core::formatter::diff::{self, DiffStats};
+use crate::tools::permissions::{Decision, PermissionPolicy};
+use crate::tools::workspace::Workspace;
+
+/// A change of a file proposed by the model, written once it is approved
+pub struct FileChange {
+    /// Resolved path of the file
+    path: PathBuf,
+    /// Path relative to the workspace root, shown to the user and the model
+    pub display: String,
+    /// Current content, `None` if the file does not exist yet
+    pub old: Option<String>,
+    /// Proposed content
+    pub new: String,
+}
+
+impl FileChange {
+    /// Reads the current content of a file in the workspace. The proposed
+    /// content starts out unchanged.
+    pub async fn read(workspace: &Workspace, path: &str) -> Result<Self, ToolError> {
+        let path = workspace.resolve(path)?;
+        let display = workspace.relative(&path).display().to_string();
+        if path.is_dir() {
+            return Err(ToolError::InvalidArgument(format!(
+                "{display} is a directory"
+            )));
+        }
+
+        let old =
+            match tokio::fs::read(&path).await {
+                Ok(bytes) => Some(String::from_utf8(bytes).map_err(|_| {
+                    ToolError::ExecutionError(format!("{display} is not a text file"))
+                })?),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+                Err(e) => {
+                    return Err(ToolError::ExecutionError(format!(
+                        "Failed to read {display}: {e}"
+                    )))
+                }
+            };
+
+        Ok(Self {
+            path,
+            display,
+            new: old.clone().unwrap_or_default(),
+            old,
+        })
+    }
+
+    /// Shows the diff of the change, approves it through the policy and
+    /// writes the file, creating missing parent directories.
+    ///
+    /// The change is checked against the permission rules as
+    /// `<tool> <path>`, e.g. `edit_file src/main.rs`.
+    pub async fn apply(
+        &self,
+        tool: &str,
+        policy: &PermissionPolicy,
+    ) -> Result<DiffStats, ToolError> {
+        let old = self.old.as_deref().unwrap_or_default();
+        if self.old.is_some() && old == self.new {
+            return Err(ToolError::InvalidArgument(format!(
+                "The change leaves {} unchanged",
+                self.display
+            )));
+        }
+
+        let action = format!("{tool} {}", self.display);
+        let decision = policy.check(&action);
+        if !matches!(decision, Decision::Deny(_)) {
+            self.show_diff()
+                .map_err(|e| ToolError::ExecutionError(e.to_string()))?;
+        }
+        if let Decision::Deny(reason) = PermissionPolicy::resolve(&action, decision)? {
+            return Err(ToolError::PermissionDenied(format!(
+                "'{action}' was denied by {reason}"
+            )));
+        }
+
+        let error = |e: io::Error| {
+            ToolError::ExecutionError(format!("Failed to write {}: {e}", self.display))
+        };
+        if let Some(parent) = self.path.parent() {
+            tokio::fs::create_dir_all(parent).await.map_err(error)?;
+        }
+        // The resolved path had no links, but one may have been created since
+        if tokio::fs::symlink_metadata(&self.path)
+            .await
+            .is_ok_and(|metadata| metadata.file_type().is_symlink())
+        {
+            return Err(ToolError::PermissionDenied(format!(
+                "{} is a symbolic link",
+                self.display
+            )));
+        }
+        tokio::fs::write(&self.path, &self.new)
+            .await
+            .map_err(error)?;
+
+        Ok(diff::diff_stats(old, &self.new))
+    }
+
+    /// Writes the diff to stderr, colorized if stderr is a terminal
+    fn show_diff(&self) -> io::Result<()> {
+        let color = if io::stderr().is_terminal() {
+            ColorChoice::Auto
+        } else {
+            ColorChoice::Never
+        };
+        let mut stderr = StandardStream::stderr(color);
+        writeln!(stderr)?;
+        diff::write_diff(&mut stderr, &self.display, self.old.as_deref(), &self.new)
+    }
+}
@@ -0,0 +1,195 @@
+use std::path::Path;
+
+use async_trait::async_trait;
+use globset::{GlobBuilder, GlobMatcher};
+use serde_json::{json, Value};
+
+use crate::core::error::ToolError;
+use crate::tools::read_file::optional_usize;
+use crate::tools::types::{Tool, ToolDefinition};
+use crate::tools::workspace::{walk_files, Workspace};
+
+/// Tool listing the files of the workspace matching a glob
+pub struct ListFilesTool {
+    workspace: Workspace,
+    /// Upper bound for the number of files returned
+    max_results: usize,
+}
+
+/// Glob selecting files by name, or by their path if it contains a `/`
+pub(super) struct FileFilter {
+    matcher: GlobMatcher,
+    match_path: bool,
+}
+
+impl FileFilter {
+    pub(super) fn new(pattern: &str) -> Result<Self, ToolError> {
+        let glob = GlobBuilder::new(pattern)
+            .literal_separator(true)
+            .build()
+            .map_err(|e| ToolError::InvalidArgument(format!("Invalid glob: {e}")))?;
+        Ok(Self {
+            matcher: glob.compile_matcher(),
+            match_path: pattern.contains('/'),
+        })
+    }
+
+    /// Checks a file found below the directory `base`
+    pub(super) fn matches(&self, base: &Path, path: &Path) -> bool {
+        let candidate = if self.match_path {
+            path.strip_prefix(base).unwrap_or(path)
+        } else {
+            path.file_name().map_or(path, Path::new)
+        };
+        self.matcher.is_match(candidate)
+    }
+}
+
+#[async_trait]
+impl Tool for ListFilesTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "list_files".to_string(),
+            description: "Lists the files in a directory of the workspace and its \
+                          subdirectories, skipping hidden files and files ignored by \
+                          .gitignore. A glob without a / matches file names (*.rs), one \
+                          with a / matches paths relative to the directory (src/**/mod.rs). \
+                          Prefer this tool over running ls or find"
+                .to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "pattern": {
+                        "type": "string",
+                        "description": "Glob the files have to match (default: all files)"
+                    },
+                    "path": {
+                        "type": "string",
+                        "description": "Directory to list, relative to the workspace root (default: the root)"
+                    },
+                    "max_results": {
+                        "type": "integer",
+                        "description": format!("Maximum number of files to return (default and maximum: {})", self.max_results),
+                        "minimum": 1
+                    }
+                },
+            }),
+        }
+    }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
+
+    async fn execute(&self, arguments: &Value) -> Result<Value, ToolError> {
+        let filter = arguments["pattern"]
+            .as_str()
+            .map(FileFilter::new)
+            .transpose()?;
+        let base = self
+            .workspace
+            .resolve(arguments["path"].as_str().unwrap_or("."))?;
+        let max_results = optional_usize(arguments, "max_results")?
+            .map_or(self.max_results, |max_results| {
+                max_results.min(self.max_results)
+            });
+
+        let workspace = self.workspace.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut files = walk_files(&base)
+                .filter(|path| {
+                    filter
+                        .as_ref()
+                        .is_none_or(|filter| filter.matches(&base, path))
+                })
+                .map(|path| workspace.relative(&path).display().to_string());
+            let listed = files.by_ref().take(max_results).collect::<Vec<_>>();
+
+            json!({
+                "files": listed,
+                "truncated": files.next().is_some(),
+            })
+        })
+        .await
+        .map_err(|e| ToolError::ExecutionError(e.to_string()))
+    }
+}
+
+impl ListFilesTool {
+    /// Creates the tool listing files within the workspace
+    pub const fn new(workspace: Workspace, max_results: usize) -> Self {
+        Self {
+            workspace,
+            max_results,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn tool(max_results: usize) -> (TempDir, ListFilesTool) {
+        let dir = TempDir::new().unwrap();
+        for path in [
+            "Cargo.toml",
+            "src/main.rs",
+            "src/tools/mod.rs",
+            "target/debug/app",
+        ] {
+            let path = dir.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+        std::fs::write(dir.path().join(".gitignore"), "/target\n").unwrap();
+        let workspace = Workspace::new(dir.path()).unwrap();
+        (dir, ListFilesTool::new(workspace, max_results))
+    }
+
+    #[tokio::test]
+    async fn test_list_files_respects_ignore_files() {
+        let (_dir, tool) = tool(100);
+
+        let result = tool.execute(&json!({})).await.unwrap();
+
+        assert_eq!(
+            result["files"],
+            json!(["Cargo.toml", "src/main.rs", "src/tools/mod.rs"])
+        );
+        assert_eq!(result["truncated"], false);
+    }
+
+    #[tokio::test]
+    async fn test_list_files_by_name_and_path_glob() {
+        let (_dir, tool) = tool(100);
+
+        let by_name = tool.execute(&json!({ "pattern": "*.rs" })).await.unwrap();
+        let by_path = tool
+            .execute(&json!({ "pattern": "*.rs", "path": "src/tools" }))
+            .await
+            .unwrap();
+        let nested = tool
+            .execute(&json!({ "pattern": "tools/*.rs", "path": "src" }))
+            .await
+            .unwrap();
+
+        assert_eq!(by_name["files"], json!(["src/main.rs", "src/tools/mod.rs"]));
+        assert_eq!(by_path["files"], json!(["src/tools/mod.rs"]));
+        assert_eq!(nested["files"], json!(["src/tools/mod.rs"]));
+    }
+
+    #[tokio::test]
+    async fn test_list_files_is_capped() {
+        let (_dir, tool) = tool(2);
+
+        let result = tool.execute(&json!({ "max_results": 10 })).await.unwrap();
+
+        assert_eq!(result["files"], json!(["Cargo.toml", "src/main.rs"]));
+        assert_eq!(result["truncated"], true);
+        assert!(matches!(
+            tool.execute(&json!({ "path": "../" })).await,
+            Err(ToolError::PermissionDenied(_))
+        ));
+    }
+}
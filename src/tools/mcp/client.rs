@@ -0,0 +1,255 @@
+use std::collections::{HashMap, HashSet};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use log::debug;
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+use tokio::sync::{oneshot, Mutex as AsyncMutex};
+
+use crate::core::error::ToolError;
+use crate::core::McpServerConfig;
+
+/// Protocol revision requested in the handshake
+const PROTOCOL_VERSION: &str = "2025-06-18";
+/// JSON-RPC error code for requests of unsupported methods
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// Requests waiting for their response by id, `None` once the server exited
+type Pending = Arc<Mutex<Option<HashMap<u64, oneshot::Sender<Result<Value, ToolError>>>>>>;
+
+/// Connection to an MCP server running as a child process.
+///
+/// Messages are newline-delimited JSON-RPC. Responses are read by a background
+/// task, so requests may be sent concurrently. The server is killed when the
+/// client is dropped.
+pub struct McpClient {
+    name: String,
+    stdin: Arc<AsyncMutex<ChildStdin>>,
+    pending: Pending,
+    next_id: AtomicU64,
+    /// Time to wait for a response
+    timeout: Duration,
+    _child: Child,
+}
+
+impl McpClient {
+    /// Starts the server and performs the initialize handshake.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the server in the configuration
+    /// * `config` - How to start the server
+    /// * `timeout` - Time to wait for every response
+    /// * `show_stderr` - Whether to pass the server's stderr through, otherwise it is discarded
+    pub async fn start(
+        name: &str,
+        config: &McpServerConfig,
+        timeout: Duration,
+        show_stderr: bool,
+    ) -> Result<Self, ToolError> {
+        let mut child = Command::new(&config.command)
+            .args(&config.args)
+            .envs(&config.env)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(if show_stderr {
+                Stdio::inherit()
+            } else {
+                Stdio::null()
+            })
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| {
+                ToolError::ExecutionError(format!("Failed to start '{}': {e}", config.command))
+            })?;
+
+        let stdin = Arc::new(AsyncMutex::new(
+            child.stdin.take().expect("stdin should be piped"),
+        ));
+        let stdout = child.stdout.take().expect("stdout should be piped");
+        let pending: Pending = Arc::new(Mutex::new(Some(HashMap::new())));
+        tokio::spawn(read_messages(
+            name.to_string(),
+            stdout,
+            Arc::clone(&stdin),
+            Arc::clone(&pending),
+        ));
+
+        let client = Self {
+            name: name.to_string(),
+            stdin,
+            pending,
+            next_id: AtomicU64::new(1),
+            timeout,
+            _child: child,
+        };
+        let info = client
+            .request(
+                "initialize",
+                json!({
+                    "protocolVersion": PROTOCOL_VERSION,
+                    "capabilities": {},
+                    "clientInfo": {
+                        "name": env!("CARGO_PKG_NAME"),
+                        "version": env!("CARGO_PKG_VERSION"),
+                    },
+                }),
+            )
+            .await?;
+        debug!("[MCP] {name} initialized: {info}");
+        client.notify("notifications/initialized").await?;
+        Ok(client)
+    }
+
+    /// Lists the tools of the server, following pagination.
+    ///
+    /// Fails if the server sends a cursor it has sent before, which would
+    /// otherwise keep the listing going forever.
+    pub async fn list_tools(&self) -> Result<Vec<Value>, ToolError> {
+        let mut tools = Vec::new();
+        let mut cursors = HashSet::new();
+        let mut params = json!({});
+        loop {
+            let mut result = self.request("tools/list", params).await?;
+            if let Value::Array(page) = result["tools"].take() {
+                tools.extend(page);
+            }
+            match result["nextCursor"].as_str() {
+                Some(cursor) if !cursors.insert(cursor.to_string()) => {
+                    return Err(self.error(&format!("tools/list repeated the cursor '{cursor}'")));
+                }
+                Some(cursor) => params = json!({ "cursor": cursor }),
+                None => return Ok(tools),
+            }
+        }
+    }
+
+    /// Calls a tool of the server and returns the raw result
+    pub async fn call_tool(&self, name: &str, arguments: &Value) -> Result<Value, ToolError> {
+        self.request(
+            "tools/call",
+            json!({ "name": name, "arguments": arguments }),
+        )
+        .await
+    }
+
+    /// Sends a request and waits for its response
+    async fn request(&self, method: &str, params: Value) -> Result<Value, ToolError> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (sender, receiver) = oneshot::channel();
+        self.pending
+            .lock()
+            .unwrap()
+            .as_mut()
+            .ok_or_else(|| self.error("the server has exited"))?
+            .insert(id, sender);
+
+        let message = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        if let Err(e) = send(&self.stdin, &message).await {
+            self.forget(id);
+            return Err(self.error(&format!("failed to send {method}: {e}")));
+        }
+
+        match tokio::time::timeout(self.timeout, receiver).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err(self.error("the server has exited")),
+            Err(_) => {
+                self.forget(id);
+                Err(self.error(&format!(
+                    "{method} timed out after {}s",
+                    self.timeout.as_secs()
+                )))
+            }
+        }
+    }
+
+    /// Sends a notification, which has no response
+    async fn notify(&self, method: &str) -> Result<(), ToolError> {
+        send(&self.stdin, &json!({ "jsonrpc": "2.0", "method": method }))
+            .await
+            .map_err(|e| self.error(&format!("failed to send {method}: {e}")))
+    }
+
+    fn forget(&self, id: u64) {
+        if let Some(pending) = self.pending.lock().unwrap().as_mut() {
+            pending.remove(&id);
+        }
+    }
+
+    fn error(&self, message: &str) -> ToolError {
+        ToolError::ExecutionError(format!("MCP server '{}': {message}", self.name))
+    }
+}
+
+/// Writes a message as a single line
+async fn send(stdin: &AsyncMutex<ChildStdin>, message: &Value) -> std::io::Result<()> {
+    let mut line = message.to_string();
+    line.push('\n');
+    let mut stdin = stdin.lock().await;
+    stdin.write_all(line.as_bytes()).await?;
+    stdin.flush().await
+}
+
+/// Dispatches the responses of the server until it exits, then fails all
+/// requests still waiting
+async fn read_messages(
+    name: String,
+    stdout: ChildStdout,
+    stdin: Arc<AsyncMutex<ChildStdin>>,
+    pending: Pending,
+) {
+    let mut lines = BufReader::new(stdout).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let Ok(message) = serde_json::from_str::<Value>(&line) else {
+            debug!("[MCP] {name} wrote a line that is not JSON: {line}");
+            continue;
+        };
+
+        match (message.get("id"), message["method"].as_str()) {
+            // Requests from the server, of which only pings are supported
+            (Some(id), Some(method)) => {
+                let reply = if method == "ping" {
+                    json!({ "jsonrpc": "2.0", "id": id, "result": {} })
+                } else {
+                    json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": { "code": METHOD_NOT_FOUND, "message": format!("Unsupported method {method}") },
+                    })
+                };
+                if send(&stdin, &reply).await.is_err() {
+                    break;
+                }
+            }
+            (Some(id), None) => {
+                let sender = id.as_u64().and_then(|id| {
+                    pending
+                        .lock()
+                        .unwrap()
+                        .as_mut()
+                        .and_then(|pending| pending.remove(&id))
+                });
+                if let Some(sender) = sender {
+                    let _ = sender.send(response_result(&name, message));
+                }
+            }
+            (None, _) => debug!("[MCP] {name} notification: {line}"),
+        }
+    }
+    pending.lock().unwrap().take();
+}
+
+/// Extracts the result of a response, or its error
+fn response_result(name: &str, mut response: Value) -> Result<Value, ToolError> {
+    if let Some(error) = response.get("error") {
+        return Err(ToolError::ExecutionError(format!(
+            "MCP server '{name}': {} (code {})",
+            error["message"].as_str().unwrap_or("unknown error"),
+            error["code"]
+        )));
+    }
+    Ok(response["result"].take())
+}
@@ -0,0 +1,231 @@
+mod client;
+mod tool;
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::future::join_all;
+
+pub use client::McpClient;
+pub use tool::McpTool;
+
+use crate::core::error::ToolError;
+use crate::core::McpServerConfig;
+use crate::tools::{PermissionPolicy, Tool, ToolRegistry};
+
+/// Starts the configured MCP servers and registers their tools.
+///
+/// Servers start concurrently. A server failing to start or to list its tools
+/// is reported on stderr and skipped, so that the other tools stay available.
+/// Tools whose name is already taken, by a built-in tool, a custom tool or a
+/// tool of another server, are skipped with a warning as well.
+///
+/// # Arguments
+/// * `registry` - The registry receiving the tools
+/// * `servers` - The configured servers by name
+/// * `policy` - The policy approving calls of tools that are not read-only
+/// * `timeout` - Time to wait for every response of a server
+/// * `show_stderr` - Whether to pass the servers' stderr through
+pub async fn register_servers(
+    registry: &mut ToolRegistry,
+    servers: &BTreeMap<String, McpServerConfig>,
+    policy: &PermissionPolicy,
+    timeout: Duration,
+    show_stderr: bool,
+) {
+    let started = join_all(servers.iter().map(|(name, config)| async move {
+        let client = McpClient::start(name, config, timeout, show_stderr).await?;
+        let tools = client.list_tools().await?;
+        Ok::<_, ToolError>((Arc::new(client), tools))
+    }))
+    .await;
+
+    for (name, result) in servers.keys().zip(started) {
+        match result {
+            Ok((client, tools)) => {
+                for tool in &tools {
+                    let Some(tool) = McpTool::new(name, Arc::clone(&client), tool, policy.clone())
+                    else {
+                        continue;
+                    };
+                    let qualified = tool.definition().name;
+                    if registry.get_tool(&qualified).is_some() {
+                        eprintln!(
+                            "Warning: skipping tool '{qualified}' of MCP server '{name}', which conflicts with another tool of the same name"
+                        );
+                        continue;
+                    }
+                    registry.register(tool);
+                }
+            }
+            Err(e) => eprintln!("Warning: MCP server '{name}' is unavailable: {e}"),
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::core::PermissionsConfig;
+    use crate::tools::{ApprovalMode, ToolDefinition};
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+
+    /// Stand-in server answering by pattern matching on the requests, whose
+    /// keys are serialized in alphabetical order
+    const STAND_IN: &str = r#"
+echo "stand-in starting" >&2
+while IFS= read -r line; do
+  id=$(printf '%s\n' "$line" | sed -n 's/^{"id":\([0-9]*\),.*/\1/p')
+  case "$line" in
+    *'"method":"initialize"'*)
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"protocolVersion":"2025-06-18","capabilities":{"tools":{}},"serverInfo":{"name":"stand-in","version":"1.0"}}}\n' "$id"
+      echo 'not json'
+      echo '{"jsonrpc":"2.0","method":"notifications/message","params":{"level":"info","data":"ready"}}' ;;
+    *'"cursor":"2"'*)
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"tools":[{"name":"exit","inputSchema":{"type":"object"},"annotations":{"readOnlyHint":true}}]}}\n' "$id" ;;
+    *'"method":"tools/list"'*)
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"tools":[{"name":"echo","description":"Echoes text","inputSchema":{"type":"object","properties":{"text":{"type":"string"}}}}],"nextCursor":"2"}}\n' "$id" ;;
+    *'"name":"echo"'*)
+      text=$(printf '%s\n' "$line" | sed -n 's/.*"text":"\([^"]*\)".*/\1/p')
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"content":[{"type":"text","text":"%s"}]}}\n' "$id" "$text" ;;
+    *'"name":"exit"'*)
+      exit 0 ;;
+  esac
+done
+"#;
+
+    fn server(command: &str, args: &[&str]) -> McpServerConfig {
+        McpServerConfig {
+            command: command.to_string(),
+            args: args.iter().map(ToString::to_string).collect(),
+            env: HashMap::new(),
+        }
+    }
+
+    async fn registry(mode: ApprovalMode) -> ToolRegistry {
+        let servers = BTreeMap::from([
+            ("stand-in".to_string(), server("sh", &["-c", STAND_IN])),
+            (
+                "missing".to_string(),
+                server("/nonexistent/mcp-server", &[]),
+            ),
+        ]);
+        let policy = PermissionPolicy::new(&PermissionsConfig::default(), mode).unwrap();
+        let mut registry = ToolRegistry::new();
+        register_servers(
+            &mut registry,
+            &servers,
+            &policy,
+            Duration::from_secs(10),
+            false,
+        )
+        .await;
+        registry
+    }
+
+    #[tokio::test]
+    async fn test_tools_of_stand_in_server() {
+        let registry = registry(ApprovalMode::Yes).await;
+
+        let mut names = registry
+            .get_tool_definitions()
+            .into_iter()
+            .map(|definition| definition.name)
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["stand-in__echo", "stand-in__exit"]);
+        assert!(!registry.is_read_only("stand-in__echo"));
+        assert!(registry.is_read_only("stand-in__exit"));
+
+        let echoed = registry
+            .execute_tool("stand-in__echo", &json!({ "text": "hello" }))
+            .await
+            .unwrap();
+        assert_eq!(echoed, "hello");
+
+        let exited = registry.execute_tool("stand-in__exit", &json!({})).await;
+        assert!(matches!(exited, Err(ToolError::ExecutionError(e)) if e.contains("exited")));
+        let after_exit = registry
+            .execute_tool("stand-in__echo", &json!({ "text": "hello" }))
+            .await;
+        assert!(matches!(after_exit, Err(ToolError::ExecutionError(_))));
+    }
+
+    /// Tool registered before the servers start
+    struct Existing;
+
+    #[async_trait::async_trait]
+    impl Tool for Existing {
+        fn definition(&self) -> ToolDefinition {
+            ToolDefinition {
+                name: "stand-in__echo".to_string(),
+                description: "Existing tool".to_string(),
+                parameters: json!({ "type": "object", "properties": {} }),
+            }
+        }
+
+        async fn execute(&self, _arguments: &Value) -> Result<Value, ToolError> {
+            Ok(json!("existing"))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_conflicting_tools_are_skipped() {
+        let servers = BTreeMap::from([("stand-in".to_string(), server("sh", &["-c", STAND_IN]))]);
+        let policy =
+            PermissionPolicy::new(&PermissionsConfig::default(), ApprovalMode::Yes).unwrap();
+        let mut registry = ToolRegistry::new();
+        registry.register(Existing);
+
+        register_servers(
+            &mut registry,
+            &servers,
+            &policy,
+            Duration::from_secs(10),
+            false,
+        )
+        .await;
+
+        let echo = registry.execute_tool("stand-in__echo", &json!({})).await;
+        assert_eq!(echo.unwrap(), "existing");
+        assert!(registry.get_tool("stand-in__exit").is_some());
+    }
+
+    #[tokio::test]
+    async fn test_repeated_cursor_fails_listing() {
+        let script = r#"
+while IFS= read -r line; do
+  id=$(printf '%s\n' "$line" | sed -n 's/^{"id":\([0-9]*\),.*/\1/p')
+  case "$line" in
+    *'"method":"initialize"'*)
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"protocolVersion":"2025-06-18","capabilities":{"tools":{}}}}\n' "$id" ;;
+    *'"method":"tools/list"'*)
+      printf '{"jsonrpc":"2.0","id":%s,"result":{"tools":[],"nextCursor":"same"}}\n' "$id" ;;
+  esac
+done
+"#;
+        let client = McpClient::start(
+            "looping",
+            &server("sh", &["-c", script]),
+            Duration::from_secs(10),
+            false,
+        )
+        .await
+        .unwrap();
+
+        let listed = client.list_tools().await;
+        assert!(matches!(listed, Err(ToolError::ExecutionError(e)) if e.contains("'same'")));
+    }
+
+    #[tokio::test]
+    async fn test_calls_are_approved_by_policy() {
+        let registry = registry(ApprovalMode::Never).await;
+
+        let denied = registry
+            .execute_tool("stand-in__echo", &json!({ "text": "hello" }))
+            .await;
+        assert!(matches!(denied, Err(ToolError::PermissionDenied(_))));
+    }
+}
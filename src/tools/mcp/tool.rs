@@ -0,0 +1,143 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde_json::{json, Value};
+
+use crate::core::error::ToolError;
+use crate::tools::mcp::McpClient;
+use crate::tools::permissions::PermissionPolicy;
+use crate::tools::types::{Tool, ToolDefinition, MAX_NAME_LENGTH};
+
+/// Tool of an MCP server, forwarding calls to it
+pub struct McpTool {
+    client: Arc<McpClient>,
+    /// Name of the tool on the server
+    name: String,
+    definition: ToolDefinition,
+    /// Whether the server marked the tool as free of side effects
+    read_only: bool,
+    policy: PermissionPolicy,
+}
+
+impl McpTool {
+    /// Creates the tool from an entry of the server's tool list, named
+    /// `<server>__<tool>`. Returns `None` for entries without a name.
+    pub fn new(
+        server: &str,
+        client: Arc<McpClient>,
+        tool: &Value,
+        policy: PermissionPolicy,
+    ) -> Option<Self> {
+        let name = tool["name"].as_str()?;
+        let definition = ToolDefinition {
+            name: qualified_name(server, name),
+            description: tool["description"].as_str().unwrap_or(name).to_string(),
+            parameters: match &tool["inputSchema"] {
+                Value::Null => json!({ "type": "object", "properties": {} }),
+                schema => schema.clone(),
+            },
+        };
+        Some(Self {
+            client,
+            name: name.to_string(),
+            definition,
+            read_only: tool["annotations"]["readOnlyHint"].as_bool() == Some(true),
+            policy,
+        })
+    }
+}
+
+/// Prefixes the tool name with the server name, replacing characters that
+/// providers do not accept in tool names
+fn qualified_name(server: &str, tool: &str) -> String {
+    format!("{server}__{tool}")
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .take(MAX_NAME_LENGTH)
+        .collect()
+}
+
+/// Converts the result of a `tools/call` request into the value returned to
+/// the model: structured content if any, otherwise the text of the content
+/// blocks, or the blocks themselves if some are not text.
+fn tool_result(mut result: Value) -> Result<Value, ToolError> {
+    let content = result["content"].take();
+    let text = content.as_array().and_then(|blocks| {
+        blocks
+            .iter()
+            .map(|block| block["text"].as_str())
+            .collect::<Option<Vec<_>>>()
+            .map(|texts| texts.join("\n"))
+    });
+
+    if result["isError"].as_bool() == Some(true) {
+        return Err(ToolError::ExecutionError(
+            text.unwrap_or_else(|| content.to_string()),
+        ));
+    }
+    match result.get_mut("structuredContent") {
+        Some(structured) if !structured.is_null() => Ok(structured.take()),
+        _ => Ok(text.map_or(content, Value::String)),
+    }
+}
+
+#[async_trait]
+impl Tool for McpTool {
+    fn definition(&self) -> ToolDefinition {
+        self.definition.clone()
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    async fn execute(&self, arguments: &Value) -> Result<Value, ToolError> {
+        if !self.read_only {
+            self.policy.approve_tool(&self.definition.name, arguments)?;
+        }
+
+        tool_result(self.client.call_tool(&self.name, arguments).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_qualified_name() {
+        assert_eq!(
+            qualified_name("github", "create_issue"),
+            "github__create_issue"
+        );
+        assert_eq!(
+            qualified_name("my server", "files.read"),
+            "my_server__files_read"
+        );
+        assert_eq!(qualified_name("s", &"x".repeat(100)).len(), MAX_NAME_LENGTH);
+    }
+
+    #[test]
+    fn test_tool_result() {
+        let text = json!({ "content": [{ "type": "text", "text": "a" }, { "type": "text", "text": "b" }] });
+        let structured = json!({ "content": [], "structuredContent": { "count": 2 } });
+        let image =
+            json!({ "content": [{ "type": "image", "data": "...", "mimeType": "image/png" }] });
+        let error =
+            json!({ "content": [{ "type": "text", "text": "no such issue" }], "isError": true });
+
+        assert_eq!(tool_result(text).unwrap(), "a\nb");
+        assert_eq!(tool_result(structured).unwrap(), json!({ "count": 2 }));
+        assert_eq!(tool_result(image).unwrap()[0]["type"], "image");
+        assert!(matches!(
+            tool_result(error),
+            Err(ToolError::ExecutionError(e)) if e == "no such issue"
+        ));
+    }
+}
@@ -1,9 +1,28 @@
 pub mod command_history;
+pub mod custom_tool;
+pub mod edit_file;
 pub mod execute_command;
+mod file_change;
+pub mod list_files;
+pub mod mcp;
+pub mod permissions;
+pub mod process;
+pub mod read_file;
 pub mod registry;
+pub mod search_files;
 pub mod types;
+pub mod workspace;
+pub mod write_file;
 
 pub use command_history::CommandHistoryTool;
+pub use custom_tool::CustomTool;
+pub use edit_file::EditFileTool;
 pub use execute_command::ExecuteCommandTool;
+pub use list_files::ListFilesTool;
+pub use permissions::{ApprovalMode, PermissionPolicy};
+pub use read_file::ReadFileTool;
 pub use registry::ToolRegistry;
+pub use search_files::SearchFilesTool;
 pub use types::*;
+pub use workspace::Workspace;
+pub use write_file::WriteFileTool;
@@ -0,0 +1,395 @@
+use std::collections::VecDeque;
+use std::future::Future;
+use std::io;
+use std::process::{ExitStatus, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::process::{Child, Command};
+use tokio::sync::Notify;
+
+/// Exit code reported by shells for processes interrupted with Ctrl-C
+const INTERRUPTED_EXIT_CODE: i32 = 130;
+/// Size of the chunks read from the output pipes
+const READ_CHUNK_SIZE: usize = 8192;
+
+/// Number of commands currently running, which take over Ctrl-C from the CLI
+static RUNNING_COMMANDS: AtomicUsize = AtomicUsize::new(0);
+/// Number of running futures that Ctrl-C cancels, see [`interruptible`]
+static INTERRUPTIBLE_TASKS: AtomicUsize = AtomicUsize::new(0);
+/// Wakes the futures run by [`interruptible`] on Ctrl-C
+static INTERRUPT: Notify = Notify::const_new();
+
+/// Exits the process on Ctrl-C unless a command is running, which is killed
+/// by its runner instead, or an [`interruptible`] future is running, which is
+/// cancelled instead.
+///
+/// Once a Ctrl-C listener is registered, tokio handles SIGINT for the rest of
+/// the process, so this restores the default behaviour everywhere else.
+pub fn exit_on_interrupt() {
+    tokio::spawn(async {
+        while tokio::signal::ctrl_c().await.is_ok() {
+            if RUNNING_COMMANDS.load(Ordering::SeqCst) > 0 {
+                continue;
+            }
+            if INTERRUPTIBLE_TASKS.load(Ordering::SeqCst) > 0 {
+                INTERRUPT.notify_waiters();
+            } else {
+                std::process::exit(INTERRUPTED_EXIT_CODE);
+            }
+        }
+    });
+}
+
+/// Runs a future that Ctrl-C cancels instead of exiting the process, e.g.
+/// one turn of a chat. Returns `None` if it was cancelled.
+///
+/// A command run by the future still takes over Ctrl-C while it is running,
+/// so pressing it then only stops the command.
+pub async fn interruptible<F: Future>(future: F) -> Option<F::Output> {
+    // Created first so that no notification is missed once the task counts
+    let interrupted = INTERRUPT.notified();
+    let _guard = Guard::new(&INTERRUPTIBLE_TASKS);
+    tokio::select! {
+        output = future => Some(output),
+        () = interrupted => None,
+    }
+}
+
+/// Limits for running a command
+#[derive(Debug, Clone, Copy)]
+pub struct ProcessLimits {
+    /// Wall-clock time after which the command is killed
+    pub timeout: Duration,
+    /// Maximum number of bytes kept of stdout and of stderr each
+    pub max_output_bytes: usize,
+}
+
+/// Outcome of a command run with [`run`]
+#[derive(Debug)]
+pub struct ProcessOutput {
+    /// Exit code, `None` if the process was terminated by a signal
+    pub exit_code: Option<i32>,
+    /// Signal that terminated the process
+    pub signal: Option<i32>,
+    pub stdout: CappedOutput,
+    pub stderr: CappedOutput,
+    pub duration: Duration,
+    /// Whether the process was killed after exceeding the timeout
+    pub timed_out: bool,
+    /// Whether the process was killed because the user pressed Ctrl-C
+    pub interrupted: bool,
+}
+
+/// Counts a running command or future for as long as it is alive
+struct Guard(&'static AtomicUsize);
+
+impl Guard {
+    fn new(counter: &'static AtomicUsize) -> Self {
+        counter.fetch_add(1, Ordering::SeqCst);
+        Self(counter)
+    }
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// How waiting for the command ended
+enum Outcome {
+    Exited(ExitStatus),
+    TimedOut,
+    Interrupted,
+}
+
+/// Runs a command in its own process group, capturing its output.
+///
+/// The whole process group is killed when the command exceeds the timeout or
+/// the user presses Ctrl-C, so that children of the command do not linger.
+///
+/// # Arguments
+/// * `command` - The command to run
+/// * `limits` - The timeout and the output limit
+/// * `input` - Data written to stdin, which is empty if `None`
+pub async fn run(
+    mut command: Command,
+    limits: ProcessLimits,
+    input: Option<&[u8]>,
+) -> io::Result<ProcessOutput> {
+    #[cfg(unix)]
+    command.process_group(0);
+    command
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+
+    let _guard = Guard::new(&RUNNING_COMMANDS);
+    let start = Instant::now();
+    let mut child = command.spawn()?;
+    let mut stdout = CappedOutput::new(limits.max_output_bytes);
+    let mut stderr = CappedOutput::new(limits.max_output_bytes);
+
+    let outcome = {
+        let stdout_pipe = child.stdout.take();
+        let stderr_pipe = child.stderr.take();
+        let stdin_pipe = child.stdin.take();
+        let write_input = async {
+            if let (Some(mut pipe), Some(input)) = (stdin_pipe, input) {
+                // The command may exit without reading its input
+                let _ = pipe.write_all(input).await;
+            }
+        };
+        let completion = async {
+            let (status, stdout_read, stderr_read, ()) = tokio::join!(
+                child.wait(),
+                stdout.read_from(stdout_pipe),
+                stderr.read_from(stderr_pipe),
+                write_input
+            );
+            stdout_read?;
+            stderr_read?;
+            status
+        };
+
+        tokio::select! {
+            status = completion => Outcome::Exited(status?),
+            () = tokio::time::sleep(limits.timeout) => Outcome::TimedOut,
+            _ = tokio::signal::ctrl_c() => Outcome::Interrupted,
+        }
+    };
+
+    let status = match outcome {
+        Outcome::Exited(status) => status,
+        Outcome::TimedOut | Outcome::Interrupted => {
+            kill_process_group(&mut child)?;
+            child.wait().await?
+        }
+    };
+
+    Ok(ProcessOutput {
+        exit_code: status.code(),
+        signal: signal(status),
+        stdout,
+        stderr,
+        duration: start.elapsed(),
+        timed_out: matches!(outcome, Outcome::TimedOut),
+        interrupted: matches!(outcome, Outcome::Interrupted),
+    })
+}
+
+/// Kills the process group led by the child
+#[cfg(unix)]
+fn kill_process_group(child: &mut Child) -> io::Result<()> {
+    if let Some(pid) = child.id().and_then(|pid| libc::pid_t::try_from(pid).ok()) {
+        // SAFETY: killpg only sends a signal and does not touch any memory
+        if unsafe { libc::killpg(pid, libc::SIGKILL) } == 0 {
+            return Ok(());
+        }
+    }
+    child.start_kill()
+}
+
+#[cfg(not(unix))]
+fn kill_process_group(child: &mut Child) -> io::Result<()> {
+    child.start_kill()
+}
+
+/// Returns the signal that terminated the process, if any
+#[cfg(unix)]
+fn signal(status: ExitStatus) -> Option<i32> {
+    use std::os::unix::process::ExitStatusExt;
+    status.signal()
+}
+
+#[cfg(not(unix))]
+const fn signal(_status: ExitStatus) -> Option<i32> {
+    None
+}
+
+/// Output buffer keeping the beginning and the end of the output when it
+/// exceeds its limit, dropping the middle
+#[derive(Debug)]
+pub struct CappedOutput {
+    head: Vec<u8>,
+    tail: VecDeque<u8>,
+    limit: usize,
+    total: usize,
+}
+
+impl CappedOutput {
+    /// Creates an empty buffer keeping at most `limit` bytes
+    pub const fn new(limit: usize) -> Self {
+        Self {
+            head: Vec::new(),
+            tail: VecDeque::new(),
+            limit,
+            total: 0,
+        }
+    }
+
+    /// Appends data, keeping the first and the last half of the limit
+    pub fn push(&mut self, mut data: &[u8]) {
+        self.total += data.len();
+
+        let head_space = (self.limit / 2).saturating_sub(self.head.len());
+        let head_len = head_space.min(data.len());
+        self.head.extend_from_slice(&data[..head_len]);
+        data = &data[head_len..];
+
+        let tail_limit = self.limit - self.limit / 2;
+        self.tail.extend(data);
+        let excess = self.tail.len().saturating_sub(tail_limit);
+        self.tail.drain(..excess);
+    }
+
+    /// Returns whether part of the output was dropped
+    pub const fn is_truncated(&self) -> bool {
+        self.total > self.limit
+    }
+
+    /// Reads the pipe to its end
+    async fn read_from(&mut self, pipe: Option<impl AsyncRead + Unpin>) -> io::Result<()> {
+        let Some(mut pipe) = pipe else {
+            return Ok(());
+        };
+        let mut buffer = vec![0; READ_CHUNK_SIZE];
+        loop {
+            let read = pipe.read(&mut buffer).await?;
+            if read == 0 {
+                return Ok(());
+            }
+            self.push(&buffer[..read]);
+        }
+    }
+}
+
+impl std::fmt::Display for CappedOutput {
+    /// Writes the output, marking where bytes were dropped
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let tail: Vec<u8> = self.tail.iter().copied().collect();
+
+        write!(f, "{}", String::from_utf8_lossy(&self.head))?;
+        if self.is_truncated() {
+            let omitted = self.total - self.head.len() - tail.len();
+            write!(f, "\n[... {omitted} bytes truncated ...]\n")?;
+        }
+        write!(f, "{}", String::from_utf8_lossy(&tail))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shell(command: &str) -> Command {
+        let mut shell = Command::new("/bin/sh");
+        shell.arg("-c").arg(command);
+        shell
+    }
+
+    fn limits(timeout: Duration, max_output_bytes: usize) -> ProcessLimits {
+        ProcessLimits {
+            timeout,
+            max_output_bytes,
+        }
+    }
+
+    #[test]
+    fn test_capped_output_within_limit() {
+        let mut output = CappedOutput::new(10);
+        output.push(b"hello");
+        output.push(b"world");
+
+        assert!(!output.is_truncated());
+        assert_eq!(output.to_string(), "helloworld");
+    }
+
+    #[test]
+    fn test_capped_output_keeps_head_and_tail() {
+        let mut output = CappedOutput::new(6);
+        output.push(b"abcdefgh");
+        output.push(b"ijkl");
+
+        assert!(output.is_truncated());
+        assert_eq!(output.to_string(), "abc\n[... 6 bytes truncated ...]\njkl");
+    }
+
+    #[tokio::test]
+    async fn test_run_captures_output() {
+        let output = run(
+            shell("echo out; echo err >&2; exit 4"),
+            limits(Duration::from_secs(10), 1024),
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(output.exit_code, Some(4));
+        assert_eq!(output.stdout.to_string(), "out\n");
+        assert_eq!(output.stderr.to_string(), "err\n");
+        assert!(!output.timed_out && !output.interrupted);
+    }
+
+    #[tokio::test]
+    async fn test_run_writes_input() {
+        let output = run(
+            shell("tr a-z A-Z"),
+            limits(Duration::from_secs(10), 1024),
+            Some(b"input"),
+        )
+        .await
+        .unwrap();
+        // Input larger than the pipe buffer is dropped if the command does not read it
+        let ignored = run(
+            shell("exit 0"),
+            limits(Duration::from_secs(10), 1024),
+            Some(&vec![b'x'; 1 << 20]),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(output.stdout.to_string(), "INPUT");
+        assert_eq!(ignored.exit_code, Some(0));
+    }
+
+    #[tokio::test]
+    async fn test_run_truncates_large_output() {
+        let output = run(
+            shell("seq 1 100000"),
+            limits(Duration::from_secs(10), 100),
+            None,
+        )
+        .await
+        .unwrap();
+        let stdout = output.stdout.to_string();
+
+        assert!(output.stdout.is_truncated());
+        assert!(stdout.starts_with("1\n2\n3\n"), "{stdout}");
+        assert!(stdout.ends_with("99999\n100000\n"), "{stdout}");
+    }
+
+    #[tokio::test]
+    async fn test_timeout_kills_process_group() {
+        // The background sleep keeps stdout open unless the whole group is killed
+        let output = run(
+            shell("sleep 30 & echo started; sleep 30"),
+            limits(Duration::from_millis(200), 1024),
+            None,
+        )
+        .await
+        .unwrap();
+
+        assert!(output.timed_out);
+        assert!(output.duration < Duration::from_secs(10));
+        assert_eq!(output.stdout.to_string(), "started\n");
+        assert_eq!(output.exit_code, None);
+    }
+}
@@ -0,0 +1,499 @@
+use std::path::{Component, Path};
+
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use tokio::fs::File;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, BufReader};
+
+use crate::core::error::ToolError;
+use crate::tools::permissions::{Decision, PermissionPolicy};
+use crate::tools::types::{Tool, ToolDefinition};
+use crate::tools::workspace::Workspace;
+
+const PATH_STRING_ERROR: &str = "path must be a string";
+/// Files up to this size are read to their end to count their lines. Larger
+/// files are only read up to the requested lines.
+const COUNT_LINES_MAX_BYTES: u64 = 1024 * 1024;
+/// Parts of file names hinting at credentials, matched case-insensitively
+const SECRET_NAME_PARTS: &[&str] = &["key", "secret"];
+
+/// Tool reading text files within the workspace, with line numbers
+pub struct ReadFileTool {
+    workspace: Workspace,
+    /// Upper bound for the bytes of content returned
+    max_bytes: usize,
+    /// Approves reading files that may hold secrets
+    policy: PermissionPolicy,
+}
+
+/// Arguments of a `read_file` call
+#[derive(Debug)]
+struct ReadRequest {
+    path: String,
+    /// First line to return, starting at 1
+    start_line: usize,
+    /// Last line to return, inclusive
+    end_line: Option<usize>,
+    max_bytes: Option<usize>,
+}
+
+impl ReadRequest {
+    fn parse(arguments: &Value) -> Result<Self, ToolError> {
+        let path = arguments["path"]
+            .as_str()
+            .filter(|path| !path.trim().is_empty())
+            .ok_or_else(|| ToolError::InvalidArgument(PATH_STRING_ERROR.to_string()))?;
+        let start_line = optional_usize(arguments, "start_line")?.unwrap_or(1);
+        let end_line = optional_usize(arguments, "end_line")?;
+
+        if start_line == 0 {
+            return Err(ToolError::InvalidArgument(
+                "start_line starts at 1".to_string(),
+            ));
+        }
+        if end_line.is_some_and(|end_line| end_line < start_line) {
+            return Err(ToolError::InvalidArgument(
+                "end_line must not be before start_line".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            path: path.to_string(),
+            start_line,
+            end_line,
+            max_bytes: optional_usize(arguments, "max_bytes")?,
+        })
+    }
+
+    fn contains(&self, line: usize) -> bool {
+        line >= self.start_line && self.end_line.is_none_or(|end_line| line <= end_line)
+    }
+}
+
+/// Reads an optional non-negative integer argument
+pub(super) fn optional_usize(arguments: &Value, key: &str) -> Result<Option<usize>, ToolError> {
+    match &arguments[key] {
+        Value::Null => Ok(None),
+        value => value
+            .as_u64()
+            .map(|value| Some(usize::try_from(value).unwrap_or(usize::MAX)))
+            .ok_or_else(|| ToolError::InvalidArgument(format!("{key} must be a positive integer"))),
+    }
+}
+
+#[async_trait]
+impl Tool for ReadFileTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "read_file".to_string(),
+            description: "Reads a text file in the workspace and returns its lines prefixed \
+                          with their line numbers. Use start_line and end_line to read parts \
+                          of large files. total_lines is only reported for files up to 1 MiB. \
+                          Hidden files and files that may hold secrets need the user's approval. \
+                          Prefer this tool over running cat"
+                .to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Path of the file, relative to the workspace root"
+                    },
+                    "start_line": {
+                        "type": "integer",
+                        "description": "First line to read, starting at 1 (default: 1)",
+                        "minimum": 1
+                    },
+                    "end_line": {
+                        "type": "integer",
+                        "description": "Last line to read, inclusive (default: end of file)",
+                        "minimum": 1
+                    },
+                    "max_bytes": {
+                        "type": "integer",
+                        "description": format!("Maximum number of bytes to return (default and maximum: {})", self.max_bytes),
+                        "minimum": 1
+                    }
+                },
+                "required": ["path"]
+            }),
+        }
+    }
+
+    // Only reading files that may hold secrets asks for approval
+    fn is_read_only(&self) -> bool {
+        true
+    }
+
+    async fn execute(&self, arguments: &Value) -> Result<Value, ToolError> {
+        let request = ReadRequest::parse(arguments)?;
+        let max_bytes = request
+            .max_bytes
+            .map_or(self.max_bytes, |max_bytes| max_bytes.min(self.max_bytes));
+        self.read(&request, max_bytes).await
+    }
+}
+
+impl ReadFileTool {
+    /// Creates the tool reading files within the workspace
+    pub const fn new(workspace: Workspace, max_bytes: usize, policy: PermissionPolicy) -> Self {
+        Self {
+            workspace,
+            max_bytes,
+            policy,
+        }
+    }
+
+    /// Asks for approval to read a hidden file or a file that may hold
+    /// secrets, checked against the permission rules as `read_file <path>`
+    fn approve(&self, relative: &Path) -> Result<(), ToolError> {
+        if !is_sensitive(relative) {
+            return Ok(());
+        }
+        let action = format!("read_file {}", relative.display());
+        if let Decision::Deny(reason) =
+            PermissionPolicy::resolve(&action, self.policy.check(&action))?
+        {
+            return Err(ToolError::PermissionDenied(format!(
+                "'{action}' was denied by {reason}"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Reads the requested lines, stopping before the content exceeds `max_bytes`.
+    ///
+    /// Reading ends with the requested lines unless the file is small enough
+    /// to count all of its lines, see [`COUNT_LINES_MAX_BYTES`].
+    async fn read(&self, request: &ReadRequest, max_bytes: usize) -> Result<Value, ToolError> {
+        let path = self.workspace.resolve(&request.path)?;
+        let relative = self.workspace.relative(&path);
+        self.approve(relative)?;
+        let display = relative.display().to_string();
+        let error =
+            |e: std::io::Error| ToolError::ExecutionError(format!("Failed to read {display}: {e}"));
+
+        let metadata = tokio::fs::metadata(&path).await.map_err(error)?;
+        if metadata.is_dir() {
+            return Err(ToolError::InvalidArgument(format!(
+                "{display} is a directory"
+            )));
+        }
+        if !metadata.is_file() {
+            return Err(ToolError::InvalidArgument(format!(
+                "{display} is not a regular file"
+            )));
+        }
+        let count_lines = metadata.len() <= COUNT_LINES_MAX_BYTES;
+
+        eprintln!("Reading {display}");
+        let mut reader = BufReader::new(File::open(&path).await.map_err(error)?);
+        if is_binary(reader.fill_buf().await.map_err(error)?) {
+            return Err(ToolError::ExecutionError(format!(
+                "{display} is a binary file"
+            )));
+        }
+
+        let mut content = String::new();
+        let mut line = Vec::new();
+        let mut line_number = 0;
+        let mut end_line = None;
+        let mut truncated = false;
+        let mut at_end = false;
+        loop {
+            let wanted = request.contains(line_number + 1) && !truncated;
+            // A longer line can never fit, so only one byte more is kept
+            let limit = if wanted {
+                max_bytes.saturating_add(1)
+            } else {
+                0
+            };
+            if !read_line(&mut reader, &mut line, limit)
+                .await
+                .map_err(error)?
+            {
+                at_end = true;
+                break;
+            }
+            line_number += 1;
+            if wanted {
+                let text = String::from_utf8_lossy(&line);
+                let numbered = format!(
+                    "{line_number:>6}\t{}\n",
+                    text.trim_end_matches(['\n', '\r'])
+                );
+                if content.len() + numbered.len() > max_bytes {
+                    truncated = true;
+                } else {
+                    content.push_str(&numbered);
+                    end_line = Some(line_number);
+                }
+            }
+            line.clear();
+
+            let done = truncated || request.end_line == Some(line_number);
+            if done && !count_lines {
+                break;
+            }
+        }
+
+        if at_end && request.start_line > line_number.max(1) {
+            return Err(ToolError::InvalidArgument(format!(
+                "start_line {} is past the end of {display} ({line_number} lines)",
+                request.start_line
+            )));
+        }
+
+        Ok(json!({
+            "path": display,
+            "content": content,
+            "start_line": request.start_line,
+            "end_line": end_line,
+            "total_lines": at_end.then_some(line_number),
+            "truncated": truncated,
+        }))
+    }
+}
+
+/// Reads the next line including its newline, keeping at most `limit` bytes
+/// of it in `line`. Returns `false` at the end of the file.
+async fn read_line<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    line: &mut Vec<u8>,
+    limit: usize,
+) -> std::io::Result<bool> {
+    let mut read_any = false;
+    loop {
+        let buffer = reader.fill_buf().await?;
+        if buffer.is_empty() {
+            return Ok(read_any);
+        }
+        read_any = true;
+        let (length, complete) = buffer
+            .iter()
+            .position(|&byte| byte == b'\n')
+            .map_or((buffer.len(), false), |newline| (newline + 1, true));
+        let kept = limit.saturating_sub(line.len()).min(length);
+        line.extend_from_slice(&buffer[..kept]);
+        reader.consume(length);
+        if complete {
+            return Ok(true);
+        }
+    }
+}
+
+/// Returns whether a path within the workspace is hidden, like the files
+/// `list_files` and `search_files` skip, or its name hints at credentials,
+/// such as `.env`, `prod.env`, `server.key` or `secrets.yaml`
+fn is_sensitive(relative: &Path) -> bool {
+    let is_hidden = relative.components().any(|component| {
+        matches!(component, Component::Normal(name) if name.to_string_lossy().starts_with('.'))
+    });
+    let name = relative
+        .file_name()
+        .map(|name| name.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    is_hidden
+        || relative
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("env"))
+        || SECRET_NAME_PARTS.iter().any(|part| name.contains(part))
+}
+
+/// Treats files with NUL bytes at their start as binary
+fn is_binary(head: &[u8]) -> bool {
+    head.contains(&0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::PermissionsConfig;
+    use crate::tools::ApprovalMode;
+    use tempfile::TempDir;
+
+    fn tool(max_bytes: usize) -> (TempDir, ReadFileTool) {
+        let dir = TempDir::new().unwrap();
+        let lines = (1..=20).map(|i| format!("line {i}\n")).collect::<Vec<_>>();
+        std::fs::write(dir.path().join("lines.txt"), lines.concat()).unwrap();
+        let workspace = Workspace::new(dir.path()).unwrap();
+        let permissions = PermissionsConfig {
+            allow: vec!["read_file .github/*".to_string()],
+            deny: Vec::new(),
+        };
+        let policy = PermissionPolicy::new(&permissions, ApprovalMode::Never).unwrap();
+        (dir, ReadFileTool::new(workspace, max_bytes, policy))
+    }
+
+    #[tokio::test]
+    async fn test_read_whole_file_with_line_numbers() {
+        let (dir, tool) = tool(10_000);
+        std::fs::write(dir.path().join("small.txt"), "a\r\nb").unwrap();
+
+        let result = tool.execute(&json!({ "path": "small.txt" })).await.unwrap();
+
+        assert_eq!(result["content"], "     1\ta\n     2\tb\n");
+        assert_eq!(result["total_lines"], 2);
+        assert_eq!(result["end_line"], 2);
+        assert_eq!(result["truncated"], false);
+    }
+
+    #[tokio::test]
+    async fn test_read_line_range() {
+        let (_dir, tool) = tool(10_000);
+
+        let result = tool
+            .execute(&json!({ "path": "lines.txt", "start_line": 3, "end_line": 4 }))
+            .await
+            .unwrap();
+
+        assert_eq!(result["content"], "     3\tline 3\n     4\tline 4\n");
+        assert_eq!(result["total_lines"], 20);
+    }
+
+    #[tokio::test]
+    async fn test_read_stops_at_max_bytes() {
+        let (_dir, tool) = tool(10_000);
+
+        let result = tool
+            .execute(&json!({ "path": "lines.txt", "max_bytes": 30 }))
+            .await
+            .unwrap();
+
+        assert_eq!(result["content"], "     1\tline 1\n     2\tline 2\n");
+        assert_eq!(result["end_line"], 2);
+        assert_eq!(result["truncated"], true);
+    }
+
+    #[tokio::test]
+    async fn test_max_bytes_is_capped_by_configuration() {
+        let (_dir, tool) = tool(15);
+
+        let result = tool
+            .execute(&json!({ "path": "lines.txt", "max_bytes": 10_000 }))
+            .await
+            .unwrap();
+
+        assert_eq!(result["end_line"], 1);
+        assert_eq!(result["truncated"], true);
+    }
+
+    #[tokio::test]
+    async fn test_large_file_is_read_up_to_requested_lines() {
+        let (dir, tool) = tool(100);
+        let lines = (1..=200_000)
+            .map(|i| format!("line {i}\n"))
+            .collect::<Vec<_>>();
+        std::fs::write(dir.path().join("large.txt"), lines.concat()).unwrap();
+        std::fs::write(dir.path().join("minified.js"), "x".repeat(2_000_000)).unwrap();
+
+        let range = tool
+            .execute(&json!({ "path": "large.txt", "start_line": 2, "end_line": 3 }))
+            .await
+            .unwrap();
+        assert_eq!(range["content"], "     2\tline 2\n     3\tline 3\n");
+        assert_eq!(range["total_lines"], Value::Null);
+
+        let long_line = tool
+            .execute(&json!({ "path": "minified.js" }))
+            .await
+            .unwrap();
+        assert_eq!(long_line["content"], "");
+        assert_eq!(long_line["end_line"], Value::Null);
+        assert_eq!(long_line["truncated"], true);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_refuses_special_files() {
+        let (dir, tool) = tool(10_000);
+        let status = std::process::Command::new("mkfifo")
+            .arg(dir.path().join("pipe"))
+            .status()
+            .unwrap();
+        assert!(status.success());
+
+        let result = tool.execute(&json!({ "path": "pipe" })).await;
+
+        assert!(matches!(result, Err(ToolError::InvalidArgument(e)) if e.contains("regular")));
+    }
+
+    #[tokio::test]
+    async fn test_refuses_binary_files() {
+        let (dir, tool) = tool(10_000);
+        std::fs::write(
+            dir.path().join("image.png"),
+            b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR",
+        )
+        .unwrap();
+
+        let result = tool.execute(&json!({ "path": "image.png" })).await;
+
+        assert!(matches!(result, Err(ToolError::ExecutionError(e)) if e.contains("binary")));
+    }
+
+    #[tokio::test]
+    async fn test_files_with_secrets_need_approval() {
+        let (dir, tool) = tool(10_000);
+        for path in [
+            ".env",
+            ".git/config",
+            "config/prod.env",
+            "deploy/server.KEY",
+            "secrets.yaml",
+        ] {
+            let path = dir.path().join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "TOKEN=abc\n").unwrap();
+        }
+        std::fs::create_dir(dir.path().join(".github")).unwrap();
+        std::fs::write(dir.path().join(".github/ci.yml"), "on: push\n").unwrap();
+
+        for path in [
+            ".env",
+            ".git/config",
+            "config/prod.env",
+            "deploy/server.KEY",
+            "secrets.yaml",
+        ] {
+            let result = tool.execute(&json!({ "path": path })).await;
+            assert!(
+                matches!(result, Err(ToolError::PermissionDenied(ref e)) if e.contains("--never")),
+                "{path}: {result:?}"
+            );
+        }
+        let allowed = tool.execute(&json!({ "path": ".github/ci.yml" })).await;
+        assert_eq!(allowed.unwrap()["content"], "     1\ton: push\n");
+    }
+
+    #[tokio::test]
+    async fn test_refuses_paths_outside_workspace() {
+        let (_dir, tool) = tool(10_000);
+
+        let result = tool.execute(&json!({ "path": "../../etc/passwd" })).await;
+
+        assert!(matches!(result, Err(ToolError::PermissionDenied(_))));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_arguments() {
+        let (_dir, tool) = tool(10_000);
+
+        for arguments in [
+            json!({}),
+            json!({ "path": "lines.txt", "start_line": 0 }),
+            json!({ "path": "lines.txt", "start_line": 5, "end_line": 2 }),
+            json!({ "path": "lines.txt", "start_line": 21 }),
+            json!({ "path": "lines.txt", "max_bytes": "many" }),
+        ] {
+            assert!(
+                matches!(
+                    tool.execute(&arguments).await,
+                    Err(ToolError::InvalidArgument(_))
+                ),
+                "{arguments}"
+            );
+        }
+    }
+}
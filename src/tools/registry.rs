@@ -31,6 +31,12 @@ impl ToolRegistry {
         Some(self.tools.get(name)?.as_ref())
     }
 
+    /// Returns whether the named tool is read-only. Unknown tools are, as
+    /// calling them only produces an error
+    pub fn is_read_only(&self, name: &str) -> bool {
+        self.get_tool(name).is_none_or(Tool::is_read_only)
+    }
+
     pub async fn execute_tool(&self, name: &str, arguments: &Value) -> Result<Value, ToolError> {
         let tool = self
             .get_tool(name)
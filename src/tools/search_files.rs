@@ -0,0 +1,337 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use async_trait::async_trait;
+use regex::{Regex, RegexBuilder};
+use serde_json::{json, Value};
+
+use crate::core::error::ToolError;
+use crate::tools::list_files::FileFilter;
+use crate::tools::read_file::optional_usize;
+use crate::tools::types::{Tool, ToolDefinition};
+use crate::tools::workspace::{walk_files, Workspace};
+
+/// Upper bound for the lines of context around every match
+const MAX_CONTEXT_LINES: usize = 10;
+/// Longer lines are cut off, e.g. in minified files
+const MAX_LINE_CHARS: usize = 500;
+/// Number of bytes at the start of a file checked for NUL bytes
+const BINARY_CHECK_BYTES: u64 = 8192;
+
+/// Tool searching the files of the workspace with a regular expression
+pub struct SearchFilesTool {
+    workspace: Workspace,
+    /// Upper bound for the number of matches returned
+    max_results: usize,
+    /// Larger files are skipped
+    max_file_bytes: u64,
+}
+
+/// Arguments of a `search_files` call
+struct SearchRequest {
+    regex: Regex,
+    path: String,
+    filter: Option<FileFilter>,
+    context_lines: usize,
+    max_results: Option<usize>,
+}
+
+impl SearchRequest {
+    fn parse(arguments: &Value) -> Result<Self, ToolError> {
+        let pattern = arguments["pattern"]
+            .as_str()
+            .ok_or_else(|| ToolError::InvalidArgument("pattern must be a string".to_string()))?;
+        let regex = RegexBuilder::new(pattern)
+            .case_insensitive(arguments["case_insensitive"].as_bool().unwrap_or(false))
+            .build()
+            .map_err(|e| ToolError::InvalidArgument(format!("Invalid pattern: {e}")))?;
+
+        Ok(Self {
+            regex,
+            path: arguments["path"].as_str().unwrap_or(".").to_string(),
+            filter: arguments["glob"]
+                .as_str()
+                .map(FileFilter::new)
+                .transpose()?,
+            context_lines: optional_usize(arguments, "context_lines")?
+                .unwrap_or(0)
+                .min(MAX_CONTEXT_LINES),
+            max_results: optional_usize(arguments, "max_results")?,
+        })
+    }
+}
+
+/// Cuts off a line after `MAX_LINE_CHARS` characters
+fn truncate_line(line: &str) -> String {
+    match line.char_indices().nth(MAX_LINE_CHARS) {
+        Some((end, _)) => format!("{}...", &line[..end]),
+        None => line.to_string(),
+    }
+}
+
+/// Collects the lines of a file matching the request, as long as there is room
+fn search_file(
+    request: &SearchRequest,
+    display: &str,
+    content: &str,
+    matches: &mut Vec<Value>,
+    limit: usize,
+) -> bool {
+    let lines = content.lines().collect::<Vec<_>>();
+    let context_of = |range: std::ops::Range<usize>| {
+        lines[range]
+            .iter()
+            .map(|line| truncate_line(line))
+            .collect::<Vec<_>>()
+    };
+
+    for (index, line) in lines.iter().enumerate() {
+        if !request.regex.is_match(line) {
+            continue;
+        }
+        if matches.len() == limit {
+            return false;
+        }
+        let before = index.saturating_sub(request.context_lines);
+        let after = (index + 1 + request.context_lines).min(lines.len());
+        let mut entry = json!({
+            "path": display,
+            "line": index + 1,
+            "text": truncate_line(line),
+        });
+        if request.context_lines > 0 {
+            entry["before"] = json!(context_of(before..index));
+            entry["after"] = json!(context_of(index + 1..after));
+        }
+        matches.push(entry);
+    }
+    true
+}
+
+#[async_trait]
+impl Tool for SearchFilesTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "search_files".to_string(),
+            description: "Searches the text files in the workspace for lines matching a \
+                          regular expression (Rust regex syntax), skipping hidden files, \
+                          files ignored by .gitignore, binary files and large files. Returns \
+                          the matching lines with their path and line number. Prefer this \
+                          tool over running grep"
+                .to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "pattern": {
+                        "type": "string",
+                        "description": "Regular expression to search for"
+                    },
+                    "path": {
+                        "type": "string",
+                        "description": "File or directory to search, relative to the workspace root (default: the root)"
+                    },
+                    "glob": {
+                        "type": "string",
+                        "description": "Only search files matching this glob, by name (*.rs) or by path if it contains a / (src/**/*.rs)"
+                    },
+                    "case_insensitive": {
+                        "type": "boolean",
+                        "description": "Ignore the case of letters (default: false)"
+                    },
+                    "context_lines": {
+                        "type": "integer",
+                        "description": format!("Lines of context before and after every match (default: 0, maximum: {MAX_CONTEXT_LINES})"),
+                        "minimum": 0
+                    },
+                    "max_results": {
+                        "type": "integer",
+                        "description": format!("Maximum number of matches to return (default and maximum: {})", self.max_results),
+                        "minimum": 1
+                    }
+                },
+                "required": ["pattern"]
+            }),
+        }
+    }
+
+    fn is_read_only(&self) -> bool {
+        true
+    }
+
+    async fn execute(&self, arguments: &Value) -> Result<Value, ToolError> {
+        let request = SearchRequest::parse(arguments)?;
+        let base = self.workspace.resolve(&request.path)?;
+        let limit = request.max_results.map_or(self.max_results, |max_results| {
+            max_results.min(self.max_results)
+        });
+
+        let workspace = self.workspace.clone();
+        let max_file_bytes = self.max_file_bytes;
+        tokio::task::spawn_blocking(move || {
+            search(&workspace, &request, &base, limit, max_file_bytes)
+        })
+        .await
+        .map_err(|e| ToolError::ExecutionError(e.to_string()))
+    }
+}
+
+/// Reads a text file of at most `max_bytes`, checking its start for NUL bytes
+/// before reading the rest. Returns `None` for binary, non UTF-8, larger or
+/// unreadable files.
+fn read_text_file(path: &Path, max_bytes: u64) -> Option<String> {
+    let metadata = std::fs::metadata(path).ok()?;
+    if !metadata.is_file() || metadata.len() > max_bytes {
+        return None;
+    }
+
+    let mut file = File::open(path).ok()?;
+    let mut content = Vec::new();
+    (&mut file)
+        .take(BINARY_CHECK_BYTES)
+        .read_to_end(&mut content)
+        .ok()?;
+    if content.contains(&0) {
+        return None;
+    }
+    // The file may have grown since its size was checked
+    file.take(max_bytes.saturating_sub(BINARY_CHECK_BYTES))
+        .read_to_end(&mut content)
+        .ok()?;
+    String::from_utf8(content).ok()
+}
+
+/// Searches the files below `base`, skipping binary, non UTF-8 and large files
+fn search(
+    workspace: &Workspace,
+    request: &SearchRequest,
+    base: &Path,
+    limit: usize,
+    max_file_bytes: u64,
+) -> Value {
+    let mut matches = Vec::new();
+    let mut files_searched = 0;
+    let mut truncated = false;
+
+    let files = walk_files(base).filter(|path| {
+        request
+            .filter
+            .as_ref()
+            .is_none_or(|filter| filter.matches(base, path))
+    });
+    for path in files {
+        let Some(content) = read_text_file(&path, max_file_bytes) else {
+            continue;
+        };
+        files_searched += 1;
+        let display = workspace.relative(&path).display().to_string();
+        if !search_file(request, &display, &content, &mut matches, limit) {
+            truncated = true;
+            break;
+        }
+    }
+
+    json!({
+        "matches": matches,
+        "files_searched": files_searched,
+        "truncated": truncated,
+    })
+}
+
+impl SearchFilesTool {
+    /// Creates the tool searching files within the workspace
+    pub const fn new(workspace: Workspace, max_results: usize, max_file_bytes: u64) -> Self {
+        Self {
+            workspace,
+            max_results,
+            max_file_bytes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn tool(max_results: usize) -> (TempDir, SearchFilesTool) {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir_all(dir.path().join("src")).unwrap();
+        std::fs::write(
+            dir.path().join("src/main.rs"),
+            "use std::io;\n\nfn main() {\n    run();\n}\n\nfn run() {}\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("README.md"), "Call run() to start\n").unwrap();
+        std::fs::write(dir.path().join("data.bin"), b"fn run\0").unwrap();
+        let workspace = Workspace::new(dir.path()).unwrap();
+        (dir, SearchFilesTool::new(workspace, max_results, 1000))
+    }
+
+    #[tokio::test]
+    async fn test_search_returns_matching_lines() {
+        let (_dir, tool) = tool(100);
+
+        let result = tool
+            .execute(&json!({ "pattern": r"fn \w+\(" }))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            result["matches"],
+            json!([
+                { "path": "src/main.rs", "line": 3, "text": "fn main() {" },
+                { "path": "src/main.rs", "line": 7, "text": "fn run() {}" },
+            ])
+        );
+        assert_eq!(result["files_searched"], 2);
+        assert_eq!(result["truncated"], false);
+    }
+
+    #[tokio::test]
+    async fn test_search_with_context_and_glob() {
+        let (_dir, tool) = tool(100);
+
+        let result = tool
+            .execute(&json!({ "pattern": "RUN", "case_insensitive": true, "glob": "*.rs", "context_lines": 1 }))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            result["matches"][0],
+            json!({
+                "path": "src/main.rs",
+                "line": 4,
+                "text": "    run();",
+                "before": ["fn main() {"],
+                "after": ["}"],
+            })
+        );
+        assert_eq!(result["matches"][1]["after"], json!([]));
+    }
+
+    #[tokio::test]
+    async fn test_search_skips_large_files() {
+        let (dir, tool) = tool(100);
+        std::fs::write(dir.path().join("large.txt"), "fn run() {}\n".repeat(100)).unwrap();
+
+        let result = tool.execute(&json!({ "pattern": "fn run" })).await.unwrap();
+
+        assert_eq!(result["matches"].as_array().unwrap().len(), 1);
+        assert_eq!(result["files_searched"], 2);
+    }
+
+    #[tokio::test]
+    async fn test_search_is_capped() {
+        let (_dir, tool) = tool(1);
+
+        let result = tool.execute(&json!({ "pattern": "run" })).await.unwrap();
+
+        assert_eq!(result["matches"].as_array().unwrap().len(), 1);
+        assert_eq!(result["truncated"], true);
+        assert!(matches!(
+            tool.execute(&json!({ "pattern": "(" })).await,
+            Err(ToolError::InvalidArgument(_))
+        ));
+    }
+}
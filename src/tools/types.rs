@@ -6,6 +6,9 @@ use serde_json::Value;
 
 use crate::core::error::ToolError;
 
+/// Longest tool name accepted by the providers
+pub const MAX_NAME_LENGTH: usize = 64;
+
 /// Represents a tool call with its identifier, name, and arguments
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ToolCall {
@@ -40,6 +43,12 @@ pub trait Tool: Send + Sync {
     /// Returns the tool's definition including its name, description, and parameter schema
     fn definition(&self) -> ToolDefinition;
 
+    /// Returns whether the tool is free of side effects and needs no
+    /// confirmation. Calls of such tools may run concurrently.
+    fn is_read_only(&self) -> bool {
+        false
+    }
+
     /// Executes the tool with the provided arguments
     ///
     /// # Arguments
@@ -0,0 +1,205 @@
+use std::env;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use ignore::WalkBuilder;
+
+use crate::core::{error::ToolError, LLMError, ToolsConfig};
+
+/// Directory the file tools are restricted to
+#[derive(Debug, Clone)]
+pub struct Workspace {
+    /// Canonical path of the workspace root
+    root: PathBuf,
+}
+
+impl Workspace {
+    /// Creates a workspace rooted at the given directory, which has to exist
+    pub fn new(root: &Path) -> io::Result<Self> {
+        let root = root.canonicalize()?;
+        if !root.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::NotADirectory,
+                format!("{} is not a directory", root.display()),
+            ));
+        }
+        Ok(Self { root })
+    }
+
+    /// Creates the workspace from the configured root, defaulting to the
+    /// working directory
+    ///
+    /// # Errors
+    /// Returns a configuration error if the root is not an existing directory
+    pub fn from_config(config: &ToolsConfig) -> Result<Self, LLMError> {
+        let root = match &config.workspace_root {
+            Some(root) => root.clone(),
+            None => env::current_dir()?,
+        };
+        Self::new(&root).map_err(|e| {
+            LLMError::ConfigError(format!("Invalid workspace root {}: {e}", root.display()))
+        })
+    }
+
+    /// Returns the canonical path of the workspace root
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Resolves a path given by the model, relative to the workspace root.
+    ///
+    /// Symbolic links and `..` are resolved before checking that the path lies
+    /// within the workspace. The path itself does not need to exist, so that
+    /// files can be created, but its closest existing ancestor does.
+    ///
+    /// # Errors
+    /// Returns `PermissionDenied` for paths outside of the workspace and for
+    /// paths through symbolic links that cannot be resolved, since writing to
+    /// a dangling link creates its target wherever it points
+    pub fn resolve(&self, path: &str) -> Result<PathBuf, ToolError> {
+        let path = self.root.join(path);
+
+        // Canonicalize the longest existing prefix and append the rest
+        let mut existing = path.as_path();
+        let mut rest = Vec::new();
+        let canonical = loop {
+            if let Ok(canonical) = existing.canonicalize() {
+                break canonical;
+            }
+            if existing.symlink_metadata().is_ok() {
+                return Err(ToolError::PermissionDenied(format!(
+                    "{} is a symbolic link that cannot be resolved",
+                    existing.display()
+                )));
+            }
+            rest.push(existing.file_name().ok_or_else(|| outside(&path))?);
+            existing = existing.parent().ok_or_else(|| outside(&path))?;
+        };
+        let resolved = rest
+            .into_iter()
+            .rev()
+            .fold(canonical, |resolved, name| resolved.join(name));
+
+        let escapes = resolved
+            .components()
+            .any(|component| component == Component::ParentDir);
+        if escapes || !resolved.starts_with(&self.root) {
+            return Err(outside(&path));
+        }
+        Ok(resolved)
+    }
+
+    /// Returns the path relative to the workspace root for display
+    pub fn relative<'a>(&self, path: &'a Path) -> &'a Path {
+        path.strip_prefix(&self.root).unwrap_or(path)
+    }
+}
+
+/// Walks the files below a path of the workspace, sorted by name.
+///
+/// Hidden files, symbolic links and files excluded by `.gitignore`, `.ignore`
+/// or the global git excludes are skipped.
+pub fn walk_files(path: &Path) -> impl Iterator<Item = PathBuf> {
+    WalkBuilder::new(path)
+        .require_git(false)
+        .sort_by_file_name(Ord::cmp)
+        .build()
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            entry
+                .file_type()
+                .is_some_and(|file_type| file_type.is_file())
+        })
+        .map(ignore::DirEntry::into_path)
+}
+
+fn outside(path: &Path) -> ToolError {
+    ToolError::PermissionDenied(format!("{} is outside of the workspace", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn workspace() -> (TempDir, Workspace) {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir(dir.path().join("src")).unwrap();
+        std::fs::write(dir.path().join("src/main.rs"), "fn main() {}\n").unwrap();
+        let workspace = Workspace::new(dir.path()).unwrap();
+        (dir, workspace)
+    }
+
+    #[test]
+    fn test_resolve_paths_within_workspace() {
+        let (_dir, workspace) = workspace();
+        let main = workspace.root().join("src/main.rs");
+
+        assert_eq!(workspace.resolve("src/main.rs").unwrap(), main);
+        assert_eq!(workspace.resolve("./src/../src/main.rs").unwrap(), main);
+        assert_eq!(workspace.resolve(main.to_str().unwrap()).unwrap(), main);
+        assert_eq!(
+            workspace.resolve("src/new/lib.rs").unwrap(),
+            workspace.root().join("src/new/lib.rs")
+        );
+    }
+
+    #[test]
+    fn test_resolve_rejects_paths_outside_workspace() {
+        let (_dir, workspace) = workspace();
+
+        for path in ["..", "../x", "/etc/passwd", "src/../../x", "new/../../x"] {
+            assert!(
+                matches!(workspace.resolve(path), Err(ToolError::PermissionDenied(_))),
+                "{path}"
+            );
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_rejects_symlinks_leaving_workspace() {
+        let (dir, workspace) = workspace();
+        let outside = TempDir::new().unwrap();
+        std::os::unix::fs::symlink(outside.path(), dir.path().join("link")).unwrap();
+
+        assert!(matches!(
+            workspace.resolve("link/secret"),
+            Err(ToolError::PermissionDenied(_))
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_resolve_rejects_dangling_symlinks() {
+        let (dir, workspace) = workspace();
+        let outside = TempDir::new().unwrap();
+        let target = outside.path().join("created");
+        std::os::unix::fs::symlink(&target, dir.path().join("dangling")).unwrap();
+
+        for path in ["dangling", "dangling/file"] {
+            assert!(
+                matches!(workspace.resolve(path), Err(ToolError::PermissionDenied(_))),
+                "{path}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_walk_files_skips_ignored_and_hidden_files() {
+        let (dir, workspace) = workspace();
+        std::fs::create_dir(dir.path().join("target")).unwrap();
+        std::fs::write(dir.path().join("target/app"), "").unwrap();
+        std::fs::write(dir.path().join(".gitignore"), "target/\n").unwrap();
+        std::fs::write(dir.path().join("README.md"), "").unwrap();
+
+        let files = walk_files(workspace.root())
+            .map(|path| workspace.relative(&path).to_path_buf())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            files,
+            [PathBuf::from("README.md"), PathBuf::from("src/main.rs")]
+        );
+    }
+}
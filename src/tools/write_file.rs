@@ -0,0 +1,159 @@
+use async_trait::async_trait;
+use serde_json::{json, Value};
+
+use crate::core::error::ToolError;
+use crate::tools::file_change::FileChange;
+use crate::tools::permissions::PermissionPolicy;
+use crate::tools::types::{Tool, ToolDefinition};
+use crate::tools::workspace::Workspace;
+
+const PATH_STRING_ERROR: &str = "path must be a string";
+const CONTENT_STRING_ERROR: &str = "content must be a string";
+
+/// Tool creating or overwriting a whole file, after the user reviewed the diff
+pub struct WriteFileTool {
+    workspace: Workspace,
+    policy: PermissionPolicy,
+}
+
+#[async_trait]
+impl Tool for WriteFileTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: "write_file".to_string(),
+            description: "Creates a file in the workspace or replaces its whole content. \
+                          Missing parent directories are created. Prefer edit_file for \
+                          changes to parts of existing files. The user reviews the diff \
+                          before it is written"
+                .to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Path of the file, relative to the workspace root"
+                    },
+                    "content": {
+                        "type": "string",
+                        "description": "The complete new content of the file"
+                    }
+                },
+                "required": ["path", "content"]
+            }),
+        }
+    }
+
+    async fn execute(&self, arguments: &Value) -> Result<Value, ToolError> {
+        let path = arguments["path"]
+            .as_str()
+            .ok_or_else(|| ToolError::InvalidArgument(PATH_STRING_ERROR.to_string()))?;
+        let content = arguments["content"]
+            .as_str()
+            .ok_or_else(|| ToolError::InvalidArgument(CONTENT_STRING_ERROR.to_string()))?;
+
+        let mut change = FileChange::read(&self.workspace, path).await?;
+        content.clone_into(&mut change.new);
+
+        let stats = change.apply("write_file", &self.policy).await?;
+        Ok(json!({
+            "path": change.display,
+            "created": change.old.is_none(),
+            "lines_added": stats.added,
+            "lines_removed": stats.removed,
+        }))
+    }
+}
+
+impl WriteFileTool {
+    /// Creates the tool writing files within the workspace, approving
+    /// changes through the policy
+    pub const fn new(workspace: Workspace, policy: PermissionPolicy) -> Self {
+        Self { workspace, policy }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::PermissionsConfig;
+    use crate::tools::ApprovalMode;
+    use tempfile::TempDir;
+
+    fn tool(allow: &[&str], mode: ApprovalMode) -> (TempDir, WriteFileTool) {
+        let dir = TempDir::new().unwrap();
+        let workspace = Workspace::new(dir.path()).unwrap();
+        let config = PermissionsConfig {
+            allow: allow.iter().map(ToString::to_string).collect(),
+            deny: vec!["write_file .env".to_string()],
+        };
+        let policy = PermissionPolicy::new(&config, mode).unwrap();
+        (dir, WriteFileTool::new(workspace, policy))
+    }
+
+    #[tokio::test]
+    async fn test_creates_file_with_parent_directories() {
+        let (dir, tool) = tool(&["write_file src/*"], ApprovalMode::Never);
+
+        let result = tool
+            .execute(&json!({ "path": "src/bin/tool.rs", "content": "fn main() {}\n" }))
+            .await
+            .unwrap();
+
+        assert_eq!(result["created"], true);
+        assert_eq!(result["lines_added"], 1);
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("src/bin/tool.rs")).unwrap(),
+            "fn main() {}\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_overwrites_existing_file() {
+        let (dir, tool) = tool(&[], ApprovalMode::Yes);
+        std::fs::write(dir.path().join("notes.md"), "old\nkept\n").unwrap();
+
+        let result = tool
+            .execute(&json!({ "path": "notes.md", "content": "new\nkept\n" }))
+            .await
+            .unwrap();
+
+        assert_eq!(result["created"], false);
+        assert_eq!(result["lines_removed"], 1);
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("notes.md")).unwrap(),
+            "new\nkept\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_denied_and_outside_paths_are_not_written() {
+        let (dir, tool) = tool(&[], ApprovalMode::Yes);
+
+        let denied = tool
+            .execute(&json!({ "path": ".env", "content": "TOKEN=x" }))
+            .await;
+        let outside = tool
+            .execute(&json!({ "path": "../escape.txt", "content": "x" }))
+            .await;
+
+        assert!(matches!(denied, Err(ToolError::PermissionDenied(_))));
+        assert!(matches!(outside, Err(ToolError::PermissionDenied(_))));
+        assert!(!dir.path().join(".env").exists());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_dangling_symlink_is_not_followed() {
+        let (dir, tool) = tool(&[], ApprovalMode::Yes);
+        let outside = TempDir::new().unwrap();
+        let target = outside.path().join("authorized_keys");
+        std::os::unix::fs::symlink(&target, dir.path().join("notes.md")).unwrap();
+
+        let result = tool
+            .execute(&json!({ "path": "notes.md", "content": "ssh-ed25519 AAAA" }))
+            .await;
+
+        assert!(matches!(result, Err(ToolError::PermissionDenied(_))));
+        assert!(!target.exists());
+    }
+}